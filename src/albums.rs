@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// `(message_id, text)` pairs of one album, in arrival order.
+type Album = Vec<(i32, String)>;
+
+/// Texts and captions of recent media-group (album) messages, so `/tr_all`
+/// can find an album's siblings: the Bot API has no way to fetch them later.
+/// Albums expire `ttl` after their last message was seen.
+pub struct AlbumStore {
+    ttl: Duration,
+    albums: Mutex<HashMap<String, (Instant, Album)>>,
+}
+
+impl AlbumStore {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            albums: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn record(&self, media_group_id: &str, message_id: i32, text: &str) {
+        let mut albums = self.albums.lock().unwrap();
+        let ttl = self.ttl;
+        albums.retain(|_, (seen, _)| seen.elapsed() < ttl);
+        let (seen, items) = albums
+            .entry(media_group_id.to_string())
+            .or_insert_with(|| (Instant::now(), Vec::new()));
+        *seen = Instant::now();
+        if !items.iter().any(|(id, _)| *id == message_id) {
+            items.push((message_id, text.to_string()));
+        }
+    }
+
+    /// The album's `(message_id, text)` pairs in message order.
+    pub fn get(&self, media_group_id: &str) -> Vec<(i32, String)> {
+        let albums = self.albums.lock().unwrap();
+        let mut items = albums
+            .get(media_group_id)
+            .filter(|(seen, _)| seen.elapsed() < self.ttl)
+            .map(|(_, items)| items.clone())
+            .unwrap_or_default();
+        items.sort_by_key(|(id, _)| *id);
+        items
+    }
+}
@@ -0,0 +1,66 @@
+use crate::types::LanguageCode;
+use async_trait::async_trait;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "bot")]
+mod jsonl;
+#[cfg(feature = "bot")]
+pub use self::jsonl::JsonlAuditSink;
+
+/// One translation served: who asked, when, in which direction, and a hash
+/// of the text. The text itself only when the deployment opts in.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    /// Seconds since the Unix epoch.
+    pub at: u64,
+    pub user_id: Option<u64>,
+    pub source_lang: LanguageCode,
+    pub target_lang: LanguageCode,
+    /// Hex SHA-256 of the source text.
+    pub text_hash: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+impl AuditEntry {
+    pub fn new(
+        user_id: Option<u64>,
+        text: &str,
+        source_lang: LanguageCode,
+        target_lang: LanguageCode,
+        include_text: bool,
+    ) -> Self {
+        let at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        let text_hash = Sha256::digest(text.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        Self {
+            at,
+            user_id,
+            source_lang,
+            target_lang,
+            text_hash,
+            text: include_text.then(|| text.to_string()),
+        }
+    }
+}
+
+/// Append-only record of translations served.
+#[async_trait]
+pub trait AuditSink: Send + Sync {
+    /// Must not hold up the caller: slow sinks should queue.
+    async fn record(&self, entry: AuditEntry);
+}
+
+/// Records nothing; used when no audit log is configured.
+pub struct NoopAuditSink;
+
+#[async_trait]
+impl AuditSink for NoopAuditSink {
+    async fn record(&self, _entry: AuditEntry) {}
+}
@@ -0,0 +1,51 @@
+use super::{AuditEntry, AuditSink};
+use async_trait::async_trait;
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+/// Appends entries to a file, one JSON object per line. Entries are queued
+/// and written by a background task, so recording never waits on the disk.
+pub struct JsonlAuditSink {
+    sender: mpsc::UnboundedSender<AuditEntry>,
+}
+
+impl JsonlAuditSink {
+    /// Opens `path` for appending, creating it if needed, and starts the
+    /// writer task. Must be called within a Tokio runtime.
+    pub fn open(path: &str) -> anyhow::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let (sender, mut receiver) = mpsc::unbounded_channel::<AuditEntry>();
+        let path = path.to_string();
+        tokio::task::spawn_blocking(move || {
+            let mut writer = BufWriter::new(file);
+            while let Some(entry) = receiver.blocking_recv() {
+                let written = serde_json::to_writer(&mut writer, &entry)
+                    .map_err(std::io::Error::from)
+                    .and_then(|()| writer.write_all(b"\n"));
+                // Flush once the queue is drained, not after every entry.
+                let flushed = written.and_then(|()| {
+                    if receiver.is_empty() {
+                        writer.flush()
+                    } else {
+                        Ok(())
+                    }
+                });
+                if let Err(e) = flushed {
+                    warn!("Failed to write audit log {}: {}", path, e);
+                }
+            }
+        });
+        Ok(Self { sender })
+    }
+}
+
+#[async_trait]
+impl AuditSink for JsonlAuditSink {
+    async fn record(&self, entry: AuditEntry) {
+        if self.sender.send(entry).is_err() {
+            warn!("Audit log writer has stopped; entry dropped");
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! Replays recorded Telegram updates through the parser and article builders
+//! without a bot or provider, so a bug report can ship with a repro file.
+//!
+//! Usage: `replay <file.json>`. The file is a JSON array of records:
+//!
+//! ```json
+//! [
+//!   {
+//!     "update": { "update_id": 1, "inline_query": { … } },
+//!     "reply": { "t": "你好", "a": ["您好"], "r": "nǐ hǎo" }
+//!   }
+//! ]
+//! ```
+//!
+//! `update` is a Bot API `Update` carrying an `inline_query` or a `message`;
+//! other kinds are skipped. `reply` is the provider reply to render, in the
+//! same shape the translator parses; when omitted the input text is echoed.
+//! Result ids are dropped from the output so runs can be diffed.
+
+use rust_bot::config::Config;
+use rust_bot::inline;
+use rust_bot::types::{ProviderTranslationPayload, TranslationResult};
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use teloxide::types::{Update, UpdateKind};
+
+#[derive(Deserialize)]
+struct Record {
+    update: Update,
+    reply: Option<ProviderTranslationPayload>,
+}
+
+fn main() -> anyhow::Result<()> {
+    let path = env::args()
+        .nth(1)
+        .ok_or_else(|| anyhow::anyhow!("usage: replay <file.json>"))?;
+
+    // Credentials are never used here; placeholders only let the rest of the
+    // configuration load exactly as the bot would see it.
+    let config = Config::from_env_with_defaults(&[
+        ("BOT_TOKEN", "replay"),
+        ("TRANSLATION_API_URL", "replay"),
+        ("TRANSLATION_API_KEY", "replay"),
+        ("TRANSLATION_MODEL", "replay"),
+    ])?;
+
+    let records: Vec<Record> = serde_json::from_str(&fs::read_to_string(&path)?)?;
+    for record in records {
+        let (kind, text) = match &record.update.kind {
+            UpdateKind::InlineQuery(q) => ("inline_query", q.query.clone()),
+            UpdateKind::Message(msg) => match msg.text() {
+                Some(text) => ("message", text.to_string()),
+                None => continue,
+            },
+            _ => continue,
+        };
+        println!("== update {} ({}): {:?}", record.update.id.0, kind, text);
+
+        let parsed = match inline::parse_inline_query(&text, &config.parse_options()) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                println!("parsed: {:?}", e);
+                continue;
+            }
+        };
+        println!("parsed: {:?}", parsed);
+
+        let reply = record.reply.unwrap_or(ProviderTranslationPayload {
+            translation: parsed.text.clone(),
+            ..Default::default()
+        });
+        let translation = TranslationResult {
+            primary_text: reply.translation,
+            alternate_texts: reply.alternatives.unwrap_or_default(),
+            romanized_text: reply.romanized,
+            provider_latency_ms: 0,
+            romanized_is_fallback: false,
+            truncated: false,
+            unchanged: false,
+            part_of_speech: reply.part_of_speech,
+            example: reply.example,
+            explanation: reply.explanation,
+        };
+
+        for article in inline::build_translation_articles(&parsed, &translation, &config, None) {
+            let mut value = serde_json::to_value(&article)?;
+            if let Some(object) = value.as_object_mut() {
+                object.remove("id");
+            }
+            println!("{}", value);
+        }
+    }
+    Ok(())
+}
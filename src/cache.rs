@@ -0,0 +1,185 @@
+use crate::types::{TranslationRequest, TranslationResult};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "redis")]
+mod redis;
+#[cfg(feature = "redis")]
+pub use self::redis::RedisCache;
+
+/// Where translations are cached between requests.
+#[async_trait]
+pub trait TranslationCache: Send + Sync {
+    async fn get(&self, request: &TranslationRequest) -> Option<TranslationResult>;
+    async fn put(&self, request: TranslationRequest, result: TranslationResult);
+
+    /// Drops expired entries, returning how many were removed. Backends that
+    /// expire entries on their own have nothing to do.
+    fn sweep(&self) -> usize {
+        0
+    }
+}
+
+/// Caches nothing; used when caching is disabled.
+pub struct NoopCache;
+
+#[async_trait]
+impl TranslationCache for NoopCache {
+    async fn get(&self, _request: &TranslationRequest) -> Option<TranslationResult> {
+        None
+    }
+
+    async fn put(&self, _request: TranslationRequest, _result: TranslationResult) {}
+}
+
+struct Entry {
+    result: TranslationResult,
+    inserted: Instant,
+    last_used: Instant,
+}
+
+/// Recent translations keyed by request. Entries expire `ttl` after they
+/// were stored; beyond `capacity` the least recently used one is evicted.
+/// Expired entries are dropped on lookup and by
+/// [`sweep`](TranslationCache::sweep).
+pub struct MemoryCache {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<HashMap<TranslationRequest, Entry>>,
+}
+
+impl MemoryCache {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl TranslationCache for MemoryCache {
+    async fn get(&self, request: &TranslationRequest) -> Option<TranslationResult> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.get_mut(request)?;
+        if entry.inserted.elapsed() >= self.ttl {
+            entries.remove(request);
+            return None;
+        }
+        entry.last_used = Instant::now();
+        Some(entry.result.clone())
+    }
+
+    async fn put(&self, request: TranslationRequest, result: TranslationResult) {
+        let mut entries = self.entries.lock().unwrap();
+        if !entries.contains_key(&request) && entries.len() >= self.capacity {
+            let ttl = self.ttl;
+            entries.retain(|_, entry| entry.inserted.elapsed() < ttl);
+        }
+        while !entries.contains_key(&request) && entries.len() >= self.capacity.max(1) {
+            let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            entries.remove(&oldest);
+        }
+        let now = Instant::now();
+        entries.insert(
+            request,
+            Entry {
+                result,
+                inserted: now,
+                last_used: now,
+            },
+        );
+    }
+
+    fn sweep(&self) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.len();
+        entries.retain(|_, entry| entry.inserted.elapsed() < self.ttl);
+        before - entries.len()
+    }
+}
+
+#[cfg(all(test, feature = "bot"))]
+mod tests {
+    use super::*;
+    use crate::types::LanguageCode;
+
+    fn request(text: &str) -> TranslationRequest {
+        TranslationRequest {
+            text: text.to_string(),
+            source_lang: LanguageCode::En,
+            target_lang: LanguageCode::Zh,
+            alternatives: 0,
+            dictionary: false,
+            romanize_only: false,
+            explain: false,
+            context: None,
+        }
+    }
+
+    fn result(text: &str) -> TranslationResult {
+        TranslationResult {
+            primary_text: text.to_string(),
+            alternate_texts: Vec::new(),
+            romanized_text: None,
+            provider_latency_ms: 0,
+            romanized_is_fallback: false,
+            truncated: false,
+            unchanged: false,
+            part_of_speech: None,
+            example: None,
+            explanation: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn entries_miss_after_the_ttl() {
+        let cache = MemoryCache::new(Duration::from_millis(50), 10);
+        cache.put(request("hello"), result("你好")).await;
+        assert_eq!(
+            cache.get(&request("hello")).await.map(|r| r.primary_text),
+            Some("你好".to_string())
+        );
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert!(cache.get(&request("hello")).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn sweep_drops_expired_entries_without_lookups() {
+        let cache = MemoryCache::new(Duration::from_millis(50), 10);
+        cache.put(request("hello"), result("你好")).await;
+        cache.put(request("bye"), result("再见")).await;
+
+        assert_eq!(cache.sweep(), 0);
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(cache.sweep(), 2);
+    }
+
+    #[tokio::test]
+    async fn capacity_evicts_the_least_recently_used() {
+        let cache = MemoryCache::new(Duration::from_secs(60), 2);
+        // Spaced out so no two entries share a timestamp.
+        let tick = || tokio::time::sleep(Duration::from_millis(2));
+        cache.put(request("one"), result("一")).await;
+        tick().await;
+        cache.put(request("two"), result("二")).await;
+        tick().await;
+        cache.get(&request("one")).await;
+        tick().await;
+        cache.put(request("three"), result("三")).await;
+
+        assert!(cache.get(&request("one")).await.is_some());
+        assert!(cache.get(&request("two")).await.is_none());
+        assert!(cache.get(&request("three")).await.is_some());
+    }
+}
@@ -0,0 +1,88 @@
+use super::TranslationCache;
+use crate::types::{TranslationRequest, TranslationResult};
+use ::redis::AsyncCommands;
+use ::redis::aio::MultiplexedConnection;
+use async_trait::async_trait;
+use tokio::sync::OnceCell;
+use tracing::warn;
+
+/// Cache shared by every instance pointed at the same Redis. Entries are
+/// JSON under `{prefix}{request}` and expire via Redis' own TTL. Redis
+/// errors are logged and treated as misses, so an outage only costs speed.
+pub struct RedisCache {
+    client: ::redis::Client,
+    /// Opened on first use, since construction is synchronous.
+    connection: OnceCell<MultiplexedConnection>,
+    prefix: String,
+    ttl_secs: u64,
+}
+
+impl RedisCache {
+    pub fn new(url: &str, prefix: String, ttl_secs: u64) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: ::redis::Client::open(url)?,
+            connection: OnceCell::new(),
+            prefix,
+            ttl_secs,
+        })
+    }
+
+    async fn connection(&self) -> Option<MultiplexedConnection> {
+        let connection = self
+            .connection
+            .get_or_try_init(|| self.client.get_multiplexed_tokio_connection())
+            .await;
+        match connection {
+            Ok(connection) => Some(connection.clone()),
+            Err(e) => {
+                warn!("Could not connect to Redis cache: {}", e);
+                None
+            }
+        }
+    }
+
+    fn key(&self, request: &TranslationRequest) -> String {
+        format!(
+            "{}{}>{}:{}:{}:{}:{}:{:?}:{}",
+            self.prefix,
+            request.source_lang,
+            request.target_lang,
+            request.alternatives,
+            u8::from(request.dictionary),
+            u8::from(request.romanize_only),
+            u8::from(request.explain),
+            request.context,
+            request.text
+        )
+    }
+}
+
+#[async_trait]
+impl TranslationCache for RedisCache {
+    async fn get(&self, request: &TranslationRequest) -> Option<TranslationResult> {
+        let mut connection = self.connection().await?;
+        let raw: Option<String> = match connection.get(self.key(request)).await {
+            Ok(raw) => raw,
+            Err(e) => {
+                warn!("Redis cache lookup failed: {}", e);
+                return None;
+            }
+        };
+        serde_json::from_str(&raw?).ok()
+    }
+
+    async fn put(&self, request: TranslationRequest, result: TranslationResult) {
+        let Some(mut connection) = self.connection().await else {
+            return;
+        };
+        let Ok(value) = serde_json::to_string(&result) else {
+            return;
+        };
+        if let Err(e) = connection
+            .set_ex::<_, _, ()>(self.key(&request), value, self.ttl_secs)
+            .await
+        {
+            warn!("Redis cache store failed: {}", e);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Caps translations in flight per chat, so one busy member cannot starve
+/// everyone else in a group. A `max` of 0 means unlimited.
+pub struct ChatSlots {
+    max: usize,
+    in_flight: Mutex<HashMap<i64, usize>>,
+}
+
+/// A taken slot, returned to its chat when dropped.
+pub struct ChatSlot<'a> {
+    slots: &'a ChatSlots,
+    chat_id: i64,
+}
+
+impl ChatSlots {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max,
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes a slot for `chat_id`, or returns `None` when the chat is at its cap.
+    pub fn try_acquire(&self, chat_id: i64) -> Option<ChatSlot<'_>> {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        let count = in_flight.entry(chat_id).or_default();
+        if self.max > 0 && *count >= self.max {
+            return None;
+        }
+        *count += 1;
+        Some(ChatSlot {
+            slots: self,
+            chat_id,
+        })
+    }
+}
+
+impl Drop for ChatSlot<'_> {
+    fn drop(&mut self) {
+        let mut in_flight = self.slots.in_flight.lock().unwrap();
+        if let Some(count) = in_flight.get_mut(&self.chat_id) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                in_flight.remove(&self.chat_id);
+            }
+        }
+    }
+}
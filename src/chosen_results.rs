@@ -0,0 +1,51 @@
+use crate::history::HistoryEntry;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Translations offered as inline results, keyed by result id, so the one a
+/// user picks can be added to their history without translating it again.
+/// Entries expire after `ttl`; the oldest are evicted beyond `capacity`.
+pub struct ChosenResultStore {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<HashMap<String, (HistoryEntry, Instant)>>,
+}
+
+impl ChosenResultStore {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn insert(&self, result_id: String, entry: HistoryEntry) {
+        let mut entries = self.entries.lock().unwrap();
+        let ttl = self.ttl;
+        entries.retain(|_, (_, since)| since.elapsed() < ttl);
+        while entries.len() >= self.capacity.max(1) {
+            let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, (_, since))| *since)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            entries.remove(&oldest);
+        }
+        entries.insert(result_id, (entry, Instant::now()));
+    }
+
+    /// Returns the entry for `result_id` if it has not expired. Entries stay
+    /// in place, since deterministic ids repeat for the same query.
+    pub fn get(&self, result_id: &str) -> Option<HistoryEntry> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(result_id)
+            .filter(|(_, since)| since.elapsed() < self.ttl)
+            .map(|(entry, _)| entry.clone())
+    }
+}
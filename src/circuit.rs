@@ -0,0 +1,76 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Stops calls to a failing dependency for a cooldown period.
+///
+/// After `failure_threshold` consecutive failures the circuit opens and
+/// [`CircuitBreaker::allow`] refuses calls until `cooldown` has passed. Then a
+/// single probe is let through: success closes the circuit, failure reopens it.
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    created: Instant,
+    consecutive_failures: AtomicU32,
+    /// Milliseconds since `created` when the circuit opened; 0 while closed.
+    opened_at_ms: AtomicU64,
+    probe_in_flight: AtomicBool,
+}
+
+impl CircuitBreaker {
+    /// A `failure_threshold` of 0 disables the breaker.
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold,
+            cooldown,
+            created: Instant::now(),
+            consecutive_failures: AtomicU32::new(0),
+            opened_at_ms: AtomicU64::new(0),
+            probe_in_flight: AtomicBool::new(false),
+        }
+    }
+
+    pub fn allow(&self) -> bool {
+        let opened_at = self.opened_at_ms.load(Ordering::Acquire);
+        if opened_at == 0 {
+            return true;
+        }
+        if self.elapsed_ms() < opened_at.saturating_add(self.cooldown.as_millis() as u64) {
+            return false;
+        }
+        self.probe_in_flight
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    }
+
+    pub fn record_success(&self) {
+        self.consecutive_failures.store(0, Ordering::Release);
+        self.opened_at_ms.store(0, Ordering::Release);
+        self.probe_in_flight.store(false, Ordering::Release);
+    }
+
+    /// Hands back a probe that `allow` let through but that was never sent,
+    /// so the next request can probe instead.
+    pub fn release_probe(&self) {
+        self.probe_in_flight.store(false, Ordering::Release);
+    }
+
+    /// Records a failure, returning `true` if it (re)opened the circuit.
+    pub fn record_failure(&self) -> bool {
+        self.probe_in_flight.store(false, Ordering::Release);
+        if self.failure_threshold == 0 {
+            return false;
+        }
+        let failures = self.consecutive_failures.fetch_add(1, Ordering::AcqRel) + 1;
+        if failures < self.failure_threshold {
+            return false;
+        }
+        // Never store 0, which means "closed".
+        self.opened_at_ms
+            .store(self.elapsed_ms().max(1), Ordering::Release);
+        true
+    }
+
+    fn elapsed_ms(&self) -> u64 {
+        self.created.elapsed().as_millis() as u64
+    }
+}
@@ -0,0 +1,86 @@
+//! Bot commands: the single list both the message handler and the
+//! `setMyCommands` registration are built from.
+
+use crate::i18n::Msg;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Start,
+    Tr,
+    TrAll,
+    Romanize,
+    Explain,
+    Version,
+    Status,
+    History,
+    ClearHistory,
+    Verbosity,
+    ForgetMe,
+    Reload,
+}
+
+impl Command {
+    /// Every command, in the order Telegram lists them.
+    pub const ALL: [Command; 12] = [
+        Command::Tr,
+        Command::TrAll,
+        Command::Romanize,
+        Command::Explain,
+        Command::History,
+        Command::ClearHistory,
+        Command::Verbosity,
+        Command::Version,
+        Command::Status,
+        Command::ForgetMe,
+        Command::Reload,
+        Command::Start,
+    ];
+
+    /// Name as typed by the user, without the leading slash.
+    pub fn name(self) -> &'static str {
+        match self {
+            Command::Start => "start",
+            Command::Tr => "tr",
+            Command::TrAll => "tr_all",
+            Command::Romanize => "romanize",
+            Command::Explain => "explain",
+            Command::Version => "version",
+            Command::Status => "status",
+            Command::History => "history",
+            Command::ClearHistory => "clearhistory",
+            Command::Verbosity => "verbosity",
+            Command::ForgetMe => "forgetme",
+            Command::Reload => "reload",
+        }
+    }
+
+    /// Description shown in Telegram's command menu.
+    pub fn description(self) -> Msg {
+        match self {
+            Command::Start => Msg::CommandStart,
+            Command::Tr => Msg::CommandTr,
+            Command::TrAll => Msg::CommandTrAll,
+            Command::Romanize => Msg::CommandRomanize,
+            Command::Explain => Msg::CommandExplain,
+            Command::Version => Msg::CommandVersion,
+            Command::Status => Msg::CommandStatus,
+            Command::History => Msg::CommandHistory,
+            Command::ClearHistory => Msg::CommandClearHistory,
+            Command::Verbosity => Msg::CommandVerbosity,
+            Command::ForgetMe => Msg::CommandForgetMe,
+            Command::Reload => Msg::CommandReload,
+        }
+    }
+
+    /// Splits a message like "/tr@mybot hello" into the command and its
+    /// arguments; `None` for text that is not one of our commands.
+    pub fn parse(text: &str) -> Option<(Command, &str)> {
+        let (command, args) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        // Commands may carry a bot mention in groups, e.g. "/tr@mybot"
+        let name = command.strip_prefix('/')?.split('@').next()?;
+        Command::ALL
+            .into_iter()
+            .find(|command| command.name() == name)
+            .map(|command| (command, args))
+    }
+}
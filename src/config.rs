@@ -1,53 +1,1093 @@
-use crate::types::LanguageCode;
+use crate::error::ErrorDetail;
+use crate::i18n::UiLanguage;
+use crate::types::{LanguageCode, MixedScriptPolicy, ParseOptions, UnsupportedSourcePolicy};
 use anyhow::Context;
+use regex::Regex;
+use serde::de::DeserializeOwned;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
+use tracing::warn;
 
+/// Most stop sequences chat completions APIs accept.
+const MAX_STOP_SEQUENCES: usize = 4;
+/// Upper bound for `MAX_ALTERNATIVES_REQUESTED`; each one costs output tokens.
+const MAX_ALTERNATIVES_REQUESTED: usize = 10;
+
+/// Proxy kinds the HTTP client can use; `socks5h` resolves names through
+/// the proxy.
+const PROXY_SCHEMES: [&str; 4] = ["http", "https", "socks5", "socks5h"];
+
+/// Highest sampling temperature chat completions APIs accept.
+const MAX_TEMPERATURE: f32 = 2.0;
+
+/// Shape of the provider's chat completions API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProviderFlavor {
+    /// `{base}/chat/completions`, bearer auth, `model` in the body.
+    OpenAi,
+    /// `{base}/openai/deployments/{deployment}/chat/completions?api-version=…`,
+    /// `api-key` header, model implied by the deployment.
+    Azure,
+}
+
+impl std::str::FromStr for ProviderFlavor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "openai" => Ok(ProviderFlavor::OpenAi),
+            "azure" => Ok(ProviderFlavor::Azure),
+            other => Err(format!("unknown provider flavor {:?}", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for ProviderFlavor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ProviderFlavor::OpenAi => "openai",
+            ProviderFlavor::Azure => "azure",
+        })
+    }
+}
+
+/// Message role the instructions are sent under. o-series models prefer
+/// `developer` to `system`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SystemRole {
+    System,
+    Developer,
+}
+
+impl std::str::FromStr for SystemRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "system" => Ok(SystemRole::System),
+            "developer" => Ok(SystemRole::Developer),
+            other => Err(format!("unknown system role {:?}", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for SystemRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SystemRole::System => "system",
+            SystemRole::Developer => "developer",
+        })
+    }
+}
+
+/// How names of people, brands and places are rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProperNounPolicy {
+    /// Left as written in the source.
+    Keep,
+    /// Spelled out in the target's script, the same way every time.
+    Transliterate,
+    /// Left to the model, like any other word.
+    Translate,
+}
+
+impl std::str::FromStr for ProperNounPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "keep" => Ok(ProperNounPolicy::Keep),
+            "transliterate" => Ok(ProperNounPolicy::Transliterate),
+            "translate" => Ok(ProperNounPolicy::Translate),
+            other => Err(format!("unknown proper noun policy {:?}", other)),
+        }
+    }
+}
+
+/// One of the results an inline translation is rendered as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InlineResult {
+    Primary,
+    Romanized,
+    Alternatives,
+    Explanation,
+    Dictionary,
+}
+
+impl InlineResult {
+    /// The order used when none is configured.
+    pub const DEFAULT_ORDER: [InlineResult; 5] = [
+        InlineResult::Primary,
+        InlineResult::Romanized,
+        InlineResult::Alternatives,
+        InlineResult::Explanation,
+        InlineResult::Dictionary,
+    ];
+}
+
+impl std::str::FromStr for InlineResult {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "primary" => Ok(InlineResult::Primary),
+            "romanized" => Ok(InlineResult::Romanized),
+            "alternatives" => Ok(InlineResult::Alternatives),
+            "explanation" => Ok(InlineResult::Explanation),
+            "dictionary" => Ok(InlineResult::Dictionary),
+            other => Err(format!("unknown inline result {:?}", other)),
+        }
+    }
+}
+
+/// Where translations are cached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheBackend {
+    None,
+    /// In this process only.
+    Memory,
+    /// Shared through Redis; needs the `redis` feature and `REDIS_URL`.
+    Redis,
+}
+
+impl std::str::FromStr for CacheBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(CacheBackend::None),
+            "memory" => Ok(CacheBackend::Memory),
+            "redis" => Ok(CacheBackend::Redis),
+            other => Err(format!("unknown cache backend {:?}", other)),
+        }
+    }
+}
+
+/// How emoji in the source are treated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmojiPolicy {
+    /// Swapped for placeholders, so they come back exactly as sent.
+    Preserve,
+    /// Left to the model, which is asked to render them in words.
+    Translate,
+    /// Removed before translation.
+    Strip,
+}
+
+impl std::str::FromStr for EmojiPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "preserve" => Ok(EmojiPolicy::Preserve),
+            "translate" => Ok(EmojiPolicy::Translate),
+            "strip" => Ok(EmojiPolicy::Strip),
+            other => Err(format!("unknown emoji policy {:?}", other)),
+        }
+    }
+}
+
+/// How a translated channel post is published.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelPostMode {
+    /// As a separate post replying to the original.
+    Reply,
+    /// Appended to the original post, which needs the bot to be allowed to
+    /// edit messages of others. Posts that would grow past Telegram's limits
+    /// get a reply instead.
+    Edit,
+}
+
+impl std::str::FromStr for ChannelPostMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "reply" => Ok(ChannelPostMode::Reply),
+            "edit" => Ok(ChannelPostMode::Edit),
+            other => Err(format!("unknown channel post mode {:?}", other)),
+        }
+    }
+}
+
+/// How log lines are written. Levels come from `RUST_LOG` either way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines.
+    Pretty,
+    /// One JSON object per line, with event fields as keys, for log pipelines.
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!("unknown log format {:?}", other)),
+        }
+    }
+}
+
+/// Runtime settings, loaded from the environment (and `.env` if present).
 #[derive(Debug, Clone)]
 pub struct Config {
     pub bot_token: String,
+    /// Telegram user ids allowed to see operator details; empty means everyone.
+    pub admin_user_ids: Vec<u64>,
     pub translation_api_url: String,
     pub translation_api_key: String,
     pub translation_model: String,
+    /// Models used instead of `translation_model` for specific directions.
+    pub model_overrides: HashMap<(LanguageCode, LanguageCode), String>,
+    /// Model for inputs shorter than `accurate_model_threshold` characters.
+    pub fast_model: Option<String>,
+    /// Model for inputs of at least `accurate_model_threshold` characters.
+    pub accurate_model: Option<String>,
+    pub accurate_model_threshold: usize,
+    /// Sampling temperature, 0 for the most literal output.
+    pub temperature: f32,
+    /// Temperatures used instead of `temperature` for specific directions.
+    pub temperature_overrides: HashMap<(LanguageCode, LanguageCode), f32>,
+    /// Sent as `stop`, to cut chatty models off after the JSON.
+    pub stop_sequences: Vec<String>,
     pub default_source_lang: LanguageCode,
     pub default_target_lang: LanguageCode,
+    /// Translate everything from the default source to the default target;
+    /// `en>zh:` prefixes and detection are disabled.
+    pub lock_direction: bool,
     pub http_timeout_ms: u64,
+    /// Language of the bot's own messages.
+    pub ui_language: UiLanguage,
+    /// How much of a provider failure users see; the log always has all of it.
+    pub user_error_detail: ErrorDetail,
+    pub provider_flavor: ProviderFlavor,
+    /// `api-version` query parameter sent to Azure endpoints.
+    pub azure_api_version: String,
+    pub system_role: SystemRole,
+    /// Headers added to every provider request, e.g. org ids or an `api-key`.
+    pub extra_headers: BTreeMap<String, String>,
+    /// Send `TRANSLATION_API_KEY` as a bearer token. Disable when the key is
+    /// passed in one of `extra_headers` instead.
+    pub provider_bearer_auth: bool,
+    /// Proxy for provider requests: `http://`, `https://` or `socks5://`.
+    pub proxy_url: Option<String>,
+    /// Without `proxy_url`, honor `HTTPS_PROXY` and friends.
+    pub use_system_proxy: bool,
+    /// PEM file with extra root certificates to trust for the provider, e.g.
+    /// those of a TLS-inspecting corporate proxy.
+    pub tls_ca_cert_path: Option<String>,
+    /// DANGEROUS: accept any certificate from the provider, expired,
+    /// self-signed or for the wrong host. Anyone on the network path can then
+    /// read and alter translations and see the API key. Local development
+    /// only; prefer `tls_ca_cert_path`.
+    pub danger_accept_invalid_certs: bool,
+    pub normalize_source: bool,
+    /// Send DM replies as one message instead of translation + romanization.
+    pub compact_dm: bool,
+    /// List alternative translations under DM replies.
+    pub dm_show_alternatives: bool,
+    /// Offer to translate a DM the other way when the direction was detected
+    /// with less confidence than this (0 to 1); 0 never offers it.
+    pub swap_suggestion_confidence: f64,
+    /// Render DM translations inside an HTML block quote.
+    pub quote_translations: bool,
+    /// How long a `/tr` prompt waits for the user's reply.
+    pub pending_input_timeout_secs: u64,
+    /// How long album captions are remembered for `/tr_all`.
+    pub album_ttl_secs: u64,
+    /// Decoration before the direction in headers; empty to omit it.
+    pub header_prefix: String,
+    pub direction_arrow: String,
+    /// Link offered as an extra inline result for short inputs, with `{word}`
+    /// replaced by the URL-encoded source, e.g.
+    /// `https://en.wiktionary.org/wiki/{word}`.
+    pub dictionary_url_template: Option<String>,
+    /// Show the original text as a spoiler above inline translations.
+    pub inline_include_source: bool,
+    /// Order of the inline results; always lists every kind once.
+    pub inline_result_order: Vec<InlineResult>,
+    /// How long an inline query waits for its translation before answering
+    /// with a "still translating" result; 0 waits indefinitely.
+    pub inline_deadline_ms: u64,
+    /// Derive inline result ids from the query instead of randomly, so the
+    /// same query always yields the same ids. For tests and stable analytics.
+    pub deterministic_ids: bool,
+    /// Cap on inline results per answer; Telegram itself allows at most 50.
+    pub max_inline_results: usize,
+    pub max_alternatives: usize,
+    /// Alternatives the prompt asks the provider for at most, and the most
+    /// read from its reply, however many were requested.
+    pub max_alternatives_requested: usize,
+    /// Completions requested when alternatives are wanted; the extra ones
+    /// become alternatives. Needs a nonzero temperature to differ.
+    pub num_candidates: usize,
+    /// Answer single words with part of speech and an example sentence.
+    pub dictionary_mode: bool,
+    /// Input characters kept per source language; the rest get 2048.
+    pub max_input_chars: HashMap<LanguageCode, usize>,
+    /// Accept `explain:` queries and `/explain`, which cost extra tokens for
+    /// an explanation of idioms and cultural meaning.
+    pub explain_mode: bool,
+    pub detection_cache_size: usize,
+    /// Reject text reliably detected as another language, or guess instead.
+    pub unsupported_source: UnsupportedSourcePolicy,
+    pub max_retries: u32,
+    pub retry_backoff_ms: u64,
+    /// Upper bound for a single retry wait, including provider `Retry-After`.
+    pub retry_max_backoff_ms: u64,
+    /// Time one translation may spend on provider calls altogether: queueing,
+    /// retries and reprompts included. 0 means only per-call limits apply.
+    pub request_budget_ms: u64,
+    /// Consecutive provider failures that open the circuit; 0 disables it.
+    pub failure_threshold: u32,
+    /// Concurrent provider calls allowed; 0 means unlimited.
+    pub max_inflight_requests: usize,
+    /// Largest provider response body read, in bytes; 0 means unlimited.
+    pub max_response_bytes: usize,
+    /// Seconds between canary translations checking the provider; 0
+    /// disables the check.
+    pub provider_health_interval_secs: u64,
+    /// Translations in flight per chat; further messages get a busy reply.
+    /// 0 means unlimited.
+    pub max_inflight_per_chat: usize,
+    /// Updates handled at once across all chats before new ones are turned
+    /// away as busy; 0 means unlimited.
+    pub max_concurrent_updates: usize,
+    pub circuit_cooldown_ms: u64,
+    /// `Memory` with a `cache_capacity` of 0 is loaded as `None`.
+    pub cache_backend: CacheBackend,
+    /// Translations kept by the memory cache.
+    pub cache_capacity: usize,
+    pub cache_ttl_secs: u64,
+    /// How often expired cache entries are swept out.
+    pub cache_sweep_interval_secs: u64,
+    pub redis_url: Option<String>,
+    /// Prepended to every Redis key, so instances can share a database.
+    pub redis_key_prefix: String,
+    /// Phrases translated into the cache at startup, one per line; an
+    /// `en>zh:` prefix sets a line's direction as it would in a query.
+    pub warmup_phrases_path: Option<String>,
+    pub warmup_concurrency: usize,
+    /// Mask profanity in translations with asterisks.
+    pub safe_mode: bool,
+    /// Extra words for the profanity filter, one per line (`zh:` or `en:`
+    /// prefixes limit a word to that language).
+    pub profanity_words_path: Option<String>,
+    /// Generate pinyin locally when the provider leaves it out.
+    pub local_pinyin_fallback: bool,
+    /// Longest translation accepted; the prompt asks for less and longer
+    /// output is cut. 0 means no limit.
+    pub max_output_chars: usize,
+    /// Shown instead of an empty translation; unset, an empty translation is
+    /// reported as an error.
+    pub empty_translation_fallback: Option<String>,
+    /// Ask the provider again, more strictly, when its reply is not valid
+    /// JSON. Costs a second call for those replies, so off by default.
+    pub reprompt_on_parse_failure: bool,
+    pub log_format: LogFormat,
+    /// Logs full prompts and provider replies at debug level. Privacy
+    /// sensitive: this writes user text to the logs. Off by default.
+    pub debug_log_payloads: bool,
+    /// Fraction (0.0–1.0) of provider exchanges logged in full at info
+    /// level, for quality monitoring without `debug_log_payloads`' volume.
+    pub payload_log_sample_rate: f64,
+    /// Send DM translations as voice messages too (needs `tts_api_url`).
+    pub tts_enabled: bool,
+    pub tts_api_url: Option<String>,
+    pub tts_api_key: Option<String>,
+    pub tts_model: String,
+    pub tts_voice: String,
+    /// Target languages the TTS voice can speak.
+    pub tts_languages: Vec<LanguageCode>,
+    /// Endpoint used to read text from photos sent without a caption.
+    pub ocr_api_url: Option<String>,
+    pub ocr_api_key: Option<String>,
+    /// Translate the title of the set a sticker sent to the bot belongs to.
+    pub translate_sticker_meta: bool,
+    /// Translate posts in channels where the bot is an admin.
+    pub translate_channel_posts: bool,
+    pub channel_post_mode: ChannelPostMode,
+    /// Add a button to inline translations that shows the romanization.
+    pub romanization_button: bool,
+    /// How long romanizations behind those buttons are kept.
+    pub romanization_ttl_secs: u64,
+    pub romanization_store_capacity: usize,
+    /// Append a record of every translation served to this JSONL file.
+    pub audit_log_path: Option<String>,
+    /// Include the source text in audit records, not just its hash.
+    pub audit_log_text: bool,
+    /// Keep each user's recent translations for `/history`. Opt-in, since
+    /// it means holding on to what users translated.
+    pub store_history: bool,
+    /// Translations kept per user with `store_history`.
+    pub history_limit: usize,
+    /// Offer the user's recent translations instead of help for an empty
+    /// inline query (needs `store_history`).
+    pub empty_query_shows_history: bool,
+    /// Offer a button that continues inline queries longer than
+    /// `inline_pm_threshold` characters in the private chat, where the
+    /// translation is not squeezed into an inline result.
+    pub inline_pm_button: bool,
+    pub inline_pm_threshold: usize,
+    /// Collapse runs of spaces before translation.
+    pub collapse_whitespace: bool,
+    /// Keep URLs out of the provider's hands via placeholders.
+    pub protect_urls: bool,
+    /// Pass ``` fenced code through untouched via placeholders.
+    pub protect_code_blocks: bool,
+    /// Still translate `//` and `#` comments inside protected code.
+    pub translate_code_comments: bool,
+    pub emoji_policy: EmojiPolicy,
+    pub mixed_script_policy: MixedScriptPolicy,
+    pub proper_noun_policy: ProperNounPolicy,
+    /// With the `keep` policy, also swap capitalized words that do not just
+    /// start a sentence for placeholders, rather than relying on the prompt.
+    pub protect_proper_nouns: bool,
+    /// Ask for numbers, currency, dates and units in the target locale's
+    /// conventions ("3:30pm" → "下午3:30").
+    pub localize_numerics: bool,
+    /// Pass numbers through untouched via placeholders instead; overrides
+    /// `localize_numerics`.
+    pub protect_numerics: bool,
+    /// Convert output punctuation to the target language's style and
+    /// capitalize English output.
+    pub normalize_output_punctuation: bool,
+    /// Remove trailing notes like "(Note: …)" from translations.
+    pub strip_translator_notes: bool,
+    /// Notes to strip on top of the built-in ones.
+    pub translator_note_patterns: Vec<Regex>,
+    /// Spans matching any of these are passed through untranslated.
+    pub no_translate_patterns: Vec<Regex>,
+}
+
+/// Where settings are read from: the process environment, combined with
+/// the entries of a `.env` file. The environment itself is never modified,
+/// since other threads may be reading it.
+struct Vars(HashMap<String, String>);
+
+impl Vars {
+    /// The environment with `.env` entries added; with `file_wins`, they
+    /// also replace variables that are already set.
+    fn load(file_wins: bool) -> Self {
+        let mut vars: HashMap<String, String> = env::vars().collect();
+        if let Ok(entries) = dotenvy::dotenv_iter() {
+            for (key, value) in entries.flatten() {
+                if file_wins {
+                    vars.insert(key, value);
+                } else {
+                    vars.entry(key).or_insert(value);
+                }
+            }
+        }
+        Self(vars)
+    }
+
+    fn var(&self, key: &str) -> Result<String, env::VarError> {
+        self.0.get(key).cloned().ok_or(env::VarError::NotPresent)
+    }
 }
 
 impl Config {
+    /// Reads the configuration from environment variables and `.env`,
+    /// failing on missing required keys or malformed values.
     pub fn from_env() -> anyhow::Result<Self> {
-        dotenvy::dotenv().ok();
+        Self::from_vars(&Vars::load(false))
+    }
+
+    /// Like [`Config::from_env`], but values in `.env` replace those in the
+    /// environment, so edits to the file apply on `/reload`.
+    pub fn reload_from_env() -> anyhow::Result<Self> {
+        Self::from_vars(&Vars::load(true))
+    }
+
+    /// Like [`Config::from_env`], but without requiring `BOT_TOKEN`, for
+    /// command-line use that never talks to Telegram.
+    pub fn from_env_without_bot() -> anyhow::Result<Self> {
+        Self::from_env_with_defaults(&[("BOT_TOKEN", "")])
+    }
 
-        let bot_token = env::var("BOT_TOKEN").context("BOT_TOKEN must be set")?;
-        let translation_api_url =
-            env::var("TRANSLATION_API_URL").context("TRANSLATION_API_URL must be set")?;
+    /// Like [`Config::from_env`], with `defaults` for keys that are set
+    /// neither in the environment nor in `.env`, e.g. placeholder
+    /// credentials for tools that never send a request.
+    pub fn from_env_with_defaults(defaults: &[(&str, &str)]) -> anyhow::Result<Self> {
+        let mut vars = Vars::load(false);
+        for (key, value) in defaults {
+            vars.0
+                .entry(key.to_string())
+                .or_insert_with(|| value.to_string());
+        }
+        Self::from_vars(&vars)
+    }
+
+    /// A config read from `vars` alone, ignoring the environment and `.env`,
+    /// with placeholders for the required keys.
+    #[cfg(test)]
+    pub(crate) fn for_tests(vars: &[(&str, &str)]) -> Self {
+        Self::try_for_tests(vars).unwrap()
+    }
+
+    /// Like [`Config::for_tests`], for `vars` that may be rejected.
+    #[cfg(test)]
+    pub(crate) fn try_for_tests(vars: &[(&str, &str)]) -> anyhow::Result<Self> {
+        let required = [
+            ("BOT_TOKEN", "test"),
+            ("TRANSLATION_API_URL", "http://127.0.0.1:9/v1"),
+            ("TRANSLATION_API_KEY", "test-key"),
+            ("TRANSLATION_MODEL", "test-model"),
+        ];
+        let map = required
+            .into_iter()
+            .chain(vars.iter().copied())
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        Self::from_vars(&Vars(map))
+    }
+
+    fn from_vars(vars: &Vars) -> anyhow::Result<Self> {
+        let bot_token = secret(vars, "BOT_TOKEN")?.context("BOT_TOKEN must be set")?;
+        let admin_user_ids = vars
+            .var("ADMIN_USER_IDS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .map(|id| {
+                id.parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid user id {:?} in ADMIN_USER_IDS", id))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let translation_api_url = vars
+            .var("TRANSLATION_API_URL")
+            .context("TRANSLATION_API_URL must be set")?;
         let translation_api_key =
-            env::var("TRANSLATION_API_KEY").context("TRANSLATION_API_KEY must be set")?;
-        let translation_model =
-            env::var("TRANSLATION_MODEL").context("TRANSLATION_MODEL must be set")?;
+            secret(vars, "TRANSLATION_API_KEY")?.context("TRANSLATION_API_KEY must be set")?;
+        let translation_model = vars
+            .var("TRANSLATION_MODEL")
+            .context("TRANSLATION_MODEL must be set")?;
 
-        let default_source_lang = env::var("DEFAULT_SOURCE_LANG")
+        let model_overrides = parse_direction_map(vars, "MODEL_OVERRIDES")?;
+        let fast_model = vars.var("FAST_MODEL").ok();
+        let accurate_model = vars.var("ACCURATE_MODEL").ok();
+        let accurate_model_threshold = env_or(vars, "ACCURATE_MODEL_THRESHOLD", 500)?;
+        let temperature = env_or(vars, "TEMPERATURE", 0.0f32)?;
+        let temperature_overrides = parse_direction_map(vars, "TEMPERATURE_OVERRIDES")?;
+        let stop_sequences: Vec<String> = match vars.var("STOP_SEQUENCES") {
+            Ok(raw) => serde_json::from_str(&raw)
+                .context("STOP_SEQUENCES must be a JSON array of strings")?,
+            Err(_) => Vec::new(),
+        };
+        if stop_sequences.len() > MAX_STOP_SEQUENCES {
+            anyhow::bail!(
+                "STOP_SEQUENCES allows at most {} entries",
+                MAX_STOP_SEQUENCES
+            );
+        }
+        if stop_sequences.iter().any(String::is_empty) {
+            anyhow::bail!("STOP_SEQUENCES must not contain empty strings");
+        }
+        for value in std::iter::once(&temperature).chain(temperature_overrides.values()) {
+            if !(0.0..=MAX_TEMPERATURE).contains(value) {
+                anyhow::bail!("Temperatures must be between 0 and {}", MAX_TEMPERATURE);
+            }
+        }
+
+        let default_source_lang = vars
+            .var("DEFAULT_SOURCE_LANG")
             .unwrap_or_else(|_| "en".to_string())
             .parse()
             .map_err(|_| anyhow::anyhow!("Invalid DEFAULT_SOURCE_LANG"))?;
 
-        let default_target_lang = env::var("DEFAULT_TARGET_LANG")
+        let default_target_lang = vars
+            .var("DEFAULT_TARGET_LANG")
             .unwrap_or_else(|_| "zh".to_string())
             .parse()
             .map_err(|_| anyhow::anyhow!("Invalid DEFAULT_TARGET_LANG"))?;
 
-        let http_timeout_ms = env::var("HTTP_TIMEOUT_MS")
+        let lock_direction = env_or(vars, "LOCK_DIRECTION", false)?;
+
+        let http_timeout_ms = vars
+            .var("HTTP_TIMEOUT_MS")
             .unwrap_or_else(|_| "15000".to_string())
             .parse()
             .context("HTTP_TIMEOUT_MS must be a number")?;
 
+        let ui_language = env_or(vars, "UI_LANGUAGE", UiLanguage::En)?;
+        let user_error_detail = env_or(vars, "USER_ERROR_DETAIL", ErrorDetail::Category)?;
+
+        let provider_flavor = env_or(vars, "PROVIDER_FLAVOR", ProviderFlavor::OpenAi)?;
+        let azure_api_version = vars
+            .var("AZURE_API_VERSION")
+            .unwrap_or_else(|_| "2024-06-01".to_string());
+        let system_role = env_or(vars, "SYSTEM_ROLE", SystemRole::System)?;
+
+        let extra_headers = match vars.var("PROVIDER_EXTRA_HEADERS") {
+            Ok(raw) => serde_json::from_str(&raw)
+                .context("PROVIDER_EXTRA_HEADERS must be a JSON object of strings")?,
+            Err(_) => BTreeMap::new(),
+        };
+        let provider_bearer_auth = env_or(vars, "PROVIDER_BEARER_AUTH", true)?;
+        let proxy_url = vars.var("PROXY_URL").ok();
+        if let Some(url) = &proxy_url {
+            let valid = url.split_once("://").is_some_and(|(scheme, rest)| {
+                PROXY_SCHEMES.contains(&scheme.to_lowercase().as_str()) && !rest.is_empty()
+            });
+            if !valid {
+                anyhow::bail!(
+                    "PROXY_URL must be a URL with one of the schemes {}",
+                    PROXY_SCHEMES.join(", ")
+                );
+            }
+        }
+        let use_system_proxy = env_or(vars, "USE_SYSTEM_PROXY", true)?;
+        let tls_ca_cert_path = vars.var("TLS_CA_CERT_PATH").ok();
+        let danger_accept_invalid_certs = env_or(vars, "DANGER_ACCEPT_INVALID_CERTS", false)?;
+
+        let normalize_source = env_or(vars, "NORMALIZE_SOURCE", false)?;
+        let compact_dm = env_or(vars, "COMPACT_DM", false)?;
+        let dm_show_alternatives = env_or(vars, "DM_SHOW_ALTERNATIVES", false)?;
+        let swap_suggestion_confidence =
+            env_or(vars, "SWAP_SUGGESTION_CONFIDENCE", 0.0f64)?.clamp(0.0, 1.0);
+        let quote_translations = env_or(vars, "QUOTE_TRANSLATIONS", false)?;
+        let pending_input_timeout_secs = env_or(vars, "PENDING_INPUT_TIMEOUT_SECS", 120)?;
+        let album_ttl_secs = env_or(vars, "ALBUM_TTL_SECS", 3600)?;
+        let header_prefix = vars
+            .var("HEADER_PREFIX")
+            .unwrap_or_else(|_| "🌐".to_string());
+        let direction_arrow = vars
+            .var("DIRECTION_ARROW")
+            .unwrap_or_else(|_| "→".to_string());
+        let dictionary_url_template = vars.var("DICTIONARY_URL_TEMPLATE").ok();
+        let inline_include_source = env_or(vars, "INLINE_INCLUDE_SOURCE", false)?;
+        let inline_result_order = parse_result_order(vars, "INLINE_RESULT_ORDER");
+        let inline_deadline_ms = env_or(vars, "INLINE_DEADLINE_MS", 8000)?;
+        let deterministic_ids = env_or(vars, "DETERMINISTIC_IDS", false)?;
+        let max_inline_results = env_or(vars, "MAX_INLINE_RESULTS", 50usize)?.clamp(1, 50);
+        let max_alternatives = env_or(vars, "MAX_ALTERNATIVES", 10)?;
+        let max_alternatives_requested = env_or(vars, "MAX_ALTERNATIVES_REQUESTED", 3)?;
+        if !(1..=MAX_ALTERNATIVES_REQUESTED).contains(&max_alternatives_requested) {
+            anyhow::bail!(
+                "MAX_ALTERNATIVES_REQUESTED must be between 1 and {}",
+                MAX_ALTERNATIVES_REQUESTED
+            );
+        }
+        let num_candidates = env_or(vars, "NUM_CANDIDATES", 1usize)?.max(1);
+        let dictionary_mode = env_or(vars, "DICTIONARY_MODE", false)?;
+        let explain_mode = env_or(vars, "EXPLAIN_MODE", false)?;
+        let max_input_chars = parse_language_map(vars, "MAX_INPUT_CHARS")?;
+        let detection_cache_size = env_or(vars, "DETECTION_CACHE_SIZE", 1024)?;
+        let unsupported_source =
+            env_or(vars, "UNSUPPORTED_SOURCE", UnsupportedSourcePolicy::Reject)?;
+
+        let max_retries = env_or(vars, "PROVIDER_MAX_RETRIES", 2)?;
+        let retry_backoff_ms = env_or(vars, "PROVIDER_RETRY_BACKOFF_MS", 500)?;
+        let retry_max_backoff_ms = env_or(vars, "PROVIDER_RETRY_MAX_BACKOFF_MS", 4000)?;
+        let request_budget_ms = env_or(vars, "REQUEST_BUDGET_MS", 0)?;
+        let failure_threshold = env_or(vars, "CIRCUIT_FAILURE_THRESHOLD", 5)?;
+        let max_inflight_requests = env_or(vars, "MAX_INFLIGHT_REQUESTS", 0)?;
+        let max_response_bytes = env_or(vars, "MAX_RESPONSE_BYTES", 1024 * 1024)?;
+        let provider_health_interval_secs = env_or(vars, "PROVIDER_HEALTH_INTERVAL_SECS", 0)?;
+        let max_inflight_per_chat = env_or(vars, "MAX_INFLIGHT_PER_CHAT", 0)?;
+        let max_concurrent_updates = env_or(vars, "MAX_CONCURRENT_UPDATES", 0)?;
+        let circuit_cooldown_ms = env_or(vars, "CIRCUIT_COOLDOWN_MS", 30000)?;
+
+        let cache_capacity = env_or(vars, "CACHE_CAPACITY", 1000)?;
+        let cache_ttl_secs = env_or(vars, "CACHE_TTL_SECS", 3600)?;
+        let cache_sweep_interval_secs = env_or(vars, "CACHE_SWEEP_INTERVAL_SECS", 60u64)?.max(1);
+        let cache_backend = match env_or(vars, "CACHE_BACKEND", CacheBackend::Memory)? {
+            CacheBackend::Memory if cache_capacity == 0 => CacheBackend::None,
+            backend => backend,
+        };
+        let redis_url = vars.var("REDIS_URL").ok();
+        let redis_key_prefix = vars
+            .var("REDIS_KEY_PREFIX")
+            .unwrap_or_else(|_| "translt:".to_string());
+        if cache_backend == CacheBackend::Redis && redis_url.is_none() {
+            anyhow::bail!("REDIS_URL must be set when CACHE_BACKEND is redis");
+        }
+        let warmup_phrases_path = vars.var("WARMUP_PHRASES_PATH").ok();
+        let warmup_concurrency = env_or(vars, "WARMUP_CONCURRENCY", 4usize)?.max(1);
+
+        let safe_mode = env_or(vars, "SAFE_MODE", false)?;
+        let profanity_words_path = vars.var("PROFANITY_WORDS_PATH").ok();
+        let local_pinyin_fallback = env_or(vars, "LOCAL_PINYIN_FALLBACK", false)?;
+        let max_output_chars = env_or(vars, "MAX_OUTPUT_CHARS", 0)?;
+        let empty_translation_fallback = vars
+            .var("EMPTY_TRANSLATION_FALLBACK")
+            .ok()
+            .filter(|text| !text.trim().is_empty());
+        let reprompt_on_parse_failure = env_or(vars, "REPROMPT_ON_PARSE_FAILURE", false)?;
+        let log_format = env_or(vars, "LOG_FORMAT", LogFormat::Pretty)?;
+        let debug_log_payloads = env_or(vars, "DEBUG_LOG_PAYLOADS", false)?;
+        let payload_log_sample_rate =
+            env_or(vars, "PAYLOAD_LOG_SAMPLE_RATE", 0.0f64)?.clamp(0.0, 1.0);
+        let tts_enabled = env_or(vars, "TTS_ENABLED", false)?;
+        let tts_api_url = vars.var("TTS_API_URL").ok();
+        let tts_api_key = vars.var("TTS_API_KEY").ok();
+        let tts_model = vars
+            .var("TTS_MODEL")
+            .unwrap_or_else(|_| "tts-1".to_string());
+        let tts_voice = vars
+            .var("TTS_VOICE")
+            .unwrap_or_else(|_| "alloy".to_string());
+        let tts_languages = parse_languages(vars, "TTS_LANGUAGES", "en,zh")?;
+        if tts_enabled && tts_api_url.is_none() {
+            anyhow::bail!("TTS_API_URL must be set when TTS_ENABLED is true");
+        }
+
+        let ocr_api_url = vars.var("OCR_API_URL").ok();
+        let ocr_api_key = vars.var("OCR_API_KEY").ok();
+        let translate_sticker_meta = env_or(vars, "TRANSLATE_STICKER_META", false)?;
+        let translate_channel_posts = env_or(vars, "TRANSLATE_CHANNEL_POSTS", false)?;
+        let channel_post_mode = env_or(vars, "CHANNEL_POST_MODE", ChannelPostMode::Reply)?;
+
+        let romanization_button = env_or(vars, "ROMANIZATION_BUTTON", false)?;
+        let romanization_ttl_secs = env_or(vars, "ROMANIZATION_TTL_SECS", 86400)?;
+        let romanization_store_capacity = env_or(vars, "ROMANIZATION_STORE_CAPACITY", 10000)?;
+
+        let audit_log_path = vars.var("AUDIT_LOG_PATH").ok();
+        let audit_log_text = env_or(vars, "AUDIT_LOG_TEXT", false)?;
+        let store_history = env_or(vars, "STORE_HISTORY", false)?;
+        let history_limit = env_or(vars, "HISTORY_LIMIT", 50)?;
+        let empty_query_shows_history = env_or(vars, "EMPTY_QUERY_SHOWS_HISTORY", false)?;
+        let inline_pm_button = env_or(vars, "INLINE_PM_BUTTON", false)?;
+        let inline_pm_threshold = env_or(vars, "INLINE_PM_THRESHOLD", 200)?;
+
+        let collapse_whitespace = env_or(vars, "COLLAPSE_WHITESPACE", false)?;
+        let protect_urls = env_or(vars, "PROTECT_URLS", true)?;
+        let protect_code_blocks = env_or(vars, "PROTECT_CODE_BLOCKS", false)?;
+        let translate_code_comments = env_or(vars, "TRANSLATE_CODE_COMMENTS", false)?;
+        let emoji_policy = env_or(vars, "EMOJI_POLICY", EmojiPolicy::Preserve)?;
+        let mixed_script_policy =
+            env_or(vars, "MIXED_SCRIPT_POLICY", MixedScriptPolicy::Translate)?;
+        let proper_noun_policy = env_or(vars, "PROPER_NOUN_POLICY", ProperNounPolicy::Translate)?;
+        let protect_proper_nouns = env_or(vars, "PROTECT_PROPER_NOUNS", false)?;
+        let localize_numerics = env_or(vars, "LOCALIZE_NUMERICS", false)?;
+        let protect_numerics = env_or(vars, "PROTECT_NUMERICS", false)?;
+        let normalize_output_punctuation = env_or(vars, "NORMALIZE_OUTPUT_PUNCTUATION", false)?;
+        let strip_translator_notes = env_or(vars, "STRIP_TRANSLATOR_NOTES", false)?;
+        let translator_note_patterns = parse_patterns(vars, "TRANSLATOR_NOTE_PATTERNS")?;
+        let no_translate_patterns = parse_patterns(vars, "NO_TRANSLATE_PATTERNS")?;
+
         Ok(Self {
             bot_token,
+            admin_user_ids,
             translation_api_url,
             translation_api_key,
             translation_model,
+            model_overrides,
+            fast_model,
+            accurate_model,
+            accurate_model_threshold,
+            temperature,
+            temperature_overrides,
+            stop_sequences,
             default_source_lang,
             default_target_lang,
+            lock_direction,
             http_timeout_ms,
+            ui_language,
+            user_error_detail,
+            provider_flavor,
+            azure_api_version,
+            system_role,
+            extra_headers,
+            provider_bearer_auth,
+            proxy_url,
+            use_system_proxy,
+            tls_ca_cert_path,
+            danger_accept_invalid_certs,
+            normalize_source,
+            compact_dm,
+            dm_show_alternatives,
+            swap_suggestion_confidence,
+            quote_translations,
+            pending_input_timeout_secs,
+            album_ttl_secs,
+            header_prefix,
+            direction_arrow,
+            dictionary_url_template,
+            inline_include_source,
+            inline_result_order,
+            inline_deadline_ms,
+            deterministic_ids,
+            max_inline_results,
+            max_alternatives,
+            max_alternatives_requested,
+            num_candidates,
+            dictionary_mode,
+            explain_mode,
+            max_input_chars,
+            detection_cache_size,
+            unsupported_source,
+            max_retries,
+            retry_backoff_ms,
+            retry_max_backoff_ms,
+            request_budget_ms,
+            failure_threshold,
+            max_inflight_requests,
+            max_response_bytes,
+            provider_health_interval_secs,
+            max_inflight_per_chat,
+            max_concurrent_updates,
+            circuit_cooldown_ms,
+            cache_backend,
+            cache_capacity,
+            cache_ttl_secs,
+            cache_sweep_interval_secs,
+            redis_url,
+            redis_key_prefix,
+            warmup_phrases_path,
+            warmup_concurrency,
+            safe_mode,
+            profanity_words_path,
+            local_pinyin_fallback,
+            max_output_chars,
+            empty_translation_fallback,
+            reprompt_on_parse_failure,
+            log_format,
+            debug_log_payloads,
+            payload_log_sample_rate,
+            tts_enabled,
+            tts_api_url,
+            tts_api_key,
+            tts_model,
+            tts_voice,
+            tts_languages,
+            ocr_api_url,
+            ocr_api_key,
+            translate_sticker_meta,
+            translate_channel_posts,
+            channel_post_mode,
+            romanization_button,
+            romanization_ttl_secs,
+            romanization_store_capacity,
+            audit_log_path,
+            audit_log_text,
+            store_history,
+            history_limit,
+            empty_query_shows_history,
+            inline_pm_button,
+            inline_pm_threshold,
+            collapse_whitespace,
+            protect_urls,
+            protect_code_blocks,
+            translate_code_comments,
+            emoji_policy,
+            mixed_script_policy,
+            proper_noun_policy,
+            protect_proper_nouns,
+            localize_numerics,
+            protect_numerics,
+            normalize_output_punctuation,
+            strip_translator_notes,
+            translator_note_patterns,
+            no_translate_patterns,
         })
     }
+
+    /// Whether `user_id` may see operator details. Everyone may when no
+    /// admins are configured.
+    pub fn is_admin(&self, user_id: u64) -> bool {
+        self.admin_user_ids.is_empty() || self.admin_user_ids.contains(&user_id)
+    }
+
+    /// Whether `user_id` may change how the bot runs, e.g. with `/reload`.
+    /// Unlike [`Config::is_admin`], nobody may when no admins are configured.
+    pub fn is_operator(&self, user_id: u64) -> bool {
+        self.admin_user_ids.contains(&user_id)
+    }
+
+    /// Model for translating `chars` characters from `source` to `target`:
+    /// the direction's override if there is one, otherwise the fast or
+    /// accurate model by length, otherwise `translation_model`.
+    pub fn model_for(&self, source: LanguageCode, target: LanguageCode, chars: usize) -> &str {
+        if let Some(model) = self.model_overrides.get(&(source, target)) {
+            return model;
+        }
+        let tier = if chars >= self.accurate_model_threshold {
+            &self.accurate_model
+        } else {
+            &self.fast_model
+        };
+        tier.as_deref().unwrap_or(&self.translation_model)
+    }
+
+    /// Sampling temperature for translating from `source` to `target`.
+    pub fn temperature_for(&self, source: LanguageCode, target: LanguageCode) -> f32 {
+        self.temperature_overrides
+            .get(&(source, target))
+            .copied()
+            .unwrap_or(self.temperature)
+    }
+
+    /// Header line shown above translations, e.g. `🌐 EN → ZH`.
+    pub fn direction_header(&self, source: LanguageCode, target: LanguageCode) -> String {
+        let direction = format!(
+            "{} {} {}",
+            source.to_string().to_uppercase(),
+            self.direction_arrow,
+            target.to_string().to_uppercase()
+        );
+        if self.header_prefix.is_empty() {
+            direction
+        } else {
+            format!("{} {}", self.header_prefix, direction)
+        }
+    }
+
+    pub fn parse_options(&self) -> ParseOptions {
+        ParseOptions {
+            default_source: self.default_source_lang,
+            default_target: self.default_target_lang,
+            normalize_source: self.normalize_source,
+            max_alternatives: self.max_alternatives,
+            dictionary_mode: self.dictionary_mode,
+            explain_mode: self.explain_mode,
+            max_text_lengths: self.max_input_chars.clone(),
+            detection_cache_size: self.detection_cache_size,
+            unsupported_source: self.unsupported_source,
+            lock_direction: self.lock_direction,
+        }
+    }
+}
+
+/// Reads an optional variable, falling back to `default` when it is unset.
+fn env_or<T>(vars: &Vars, key: &str, default: T) -> anyhow::Result<T>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    match vars.var(key) {
+        Ok(raw) => raw
+            .trim()
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid value for {}: {} ({})", key, raw, e)),
+        Err(_) => Ok(default),
+    }
+}
+
+/// Reads a secret kept out of the environment: from the file named by
+/// `{key}_FILE` (as with Docker secrets), else from what `{key}_COMMAND`
+/// prints when run by `sh`, else from `key` itself. Trailing newlines are
+/// dropped from files and command output.
+fn secret(vars: &Vars, key: &str) -> anyhow::Result<Option<String>> {
+    if let Ok(path) = vars.var(&format!("{}_FILE", key)) {
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Could not read {}_FILE {:?}", key, path))?;
+        return Ok(Some(contents.trim_end_matches(['\r', '\n']).to_string()));
+    }
+    if let Ok(command) = vars.var(&format!("{}_COMMAND", key)) {
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(&command)
+            .output()
+            .with_context(|| format!("Could not run {}_COMMAND", key))?;
+        if !output.status.success() {
+            anyhow::bail!("{}_COMMAND failed ({})", key, output.status);
+        }
+        let stdout = String::from_utf8(output.stdout)
+            .with_context(|| format!("{}_COMMAND printed invalid UTF-8", key))?;
+        return Ok(Some(stdout.trim_end_matches(['\r', '\n']).to_string()));
+    }
+    Ok(vars.var(key).ok())
+}
+
+/// Reads a comma-separated list of language codes, e.g. `en,zh`.
+fn parse_languages(vars: &Vars, key: &str, default: &str) -> anyhow::Result<Vec<LanguageCode>> {
+    vars.var(key)
+        .unwrap_or_else(|_| default.to_string())
+        .split(',')
+        .map(str::trim)
+        .filter(|code| !code.is_empty())
+        .map(|code| {
+            code.parse()
+                .map_err(|_| anyhow::anyhow!("Invalid language {:?} in {}", code, key))
+        })
+        .collect()
+}
+
+/// Reads a JSON object keyed by language, e.g. `{"zh": 1000, "en": 3000}`.
+fn parse_language_map<T: DeserializeOwned>(
+    vars: &Vars,
+    key: &str,
+) -> anyhow::Result<HashMap<LanguageCode, T>> {
+    let Ok(raw) = vars.var(key) else {
+        return Ok(HashMap::new());
+    };
+    let entries: BTreeMap<String, T> = serde_json::from_str(&raw)
+        .with_context(|| format!("{} must be a JSON object keyed by language", key))?;
+    entries
+        .into_iter()
+        .map(|(code, value)| {
+            let lang = code
+                .trim()
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid language {:?} in {}", code, key))?;
+            Ok((lang, value))
+        })
+        .collect()
+}
+
+/// Reads a comma-separated list of inline results, e.g. `romanized,primary`.
+/// Unknown names are skipped with a warning, and kinds left out follow in
+/// their default order.
+fn parse_result_order(vars: &Vars, key: &str) -> Vec<InlineResult> {
+    let mut order = Vec::new();
+    for name in vars.var(key).unwrap_or_default().split(',').map(str::trim) {
+        match name.parse() {
+            Ok(kind) if !order.contains(&kind) => order.push(kind),
+            Ok(_) => {}
+            Err(_) if name.is_empty() => {}
+            Err(e) => warn!("Ignoring entry in {}: {}", key, e),
+        }
+    }
+    for kind in InlineResult::DEFAULT_ORDER {
+        if !order.contains(&kind) {
+            order.push(kind);
+        }
+    }
+    order
+}
+
+/// Reads a JSON object keyed by direction, e.g. `{"zh>en": "gpt-4o"}` or
+/// `{"zh>en": 0.0}`.
+fn parse_direction_map<T: DeserializeOwned>(
+    vars: &Vars,
+    key: &str,
+) -> anyhow::Result<HashMap<(LanguageCode, LanguageCode), T>> {
+    let Ok(raw) = vars.var(key) else {
+        return Ok(HashMap::new());
+    };
+    let entries: BTreeMap<String, T> = serde_json::from_str(&raw)
+        .with_context(|| format!("{} must be a JSON object keyed by direction", key))?;
+    entries
+        .into_iter()
+        .map(|(direction, value)| {
+            let pair = direction
+                .split_once('>')
+                .and_then(|(source, target)| {
+                    Some((source.trim().parse().ok()?, target.trim().parse().ok()?))
+                })
+                .ok_or_else(|| anyhow::anyhow!("Invalid direction {:?} in {}", direction, key))?;
+            Ok((pair, value))
+        })
+        .collect()
+}
+
+/// Reads a JSON array of regular expressions, e.g. `["\\$[^$]+\\$", "\\b[A-Z_]{3,}\\b"]`.
+fn parse_patterns(vars: &Vars, key: &str) -> anyhow::Result<Vec<Regex>> {
+    let Ok(raw) = vars.var(key) else {
+        return Ok(Vec::new());
+    };
+    let sources: Vec<String> = serde_json::from_str(&raw)
+        .with_context(|| format!("{} must be a JSON array of strings", key))?;
+    sources
+        .iter()
+        .map(|source| {
+            Regex::new(source).with_context(|| format!("Invalid pattern in {}: {}", key, source))
+        })
+        .collect()
 }
@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Inline queries handed over to the private chat through a `/start`
+/// deep link, keyed by the link's parameter (which is limited to 64
+/// characters, too few to carry the text itself). Each entry belongs to the
+/// user who sent the query and is used once; entries expire after `ttl` and
+/// the oldest are evicted beyond `capacity`.
+pub struct DeepLinkStore {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+struct Entry {
+    user_id: u64,
+    text: String,
+    since: Instant,
+}
+
+impl DeepLinkStore {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn insert(&self, key: String, user_id: u64, text: String) {
+        let mut entries = self.entries.lock().unwrap();
+        let ttl = self.ttl;
+        entries.retain(|_, entry| entry.since.elapsed() < ttl);
+        while entries.len() >= self.capacity.max(1) {
+            let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.since)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            entries.remove(&oldest);
+        }
+        let since = Instant::now();
+        entries.insert(
+            key,
+            Entry {
+                user_id,
+                text,
+                since,
+            },
+        );
+    }
+
+    /// Removes and returns the text for `key` if it has not expired and
+    /// `user_id` sent it.
+    pub fn take(&self, key: &str, user_id: u64) -> Option<String> {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.get(key)?.user_id != user_id {
+            return None;
+        }
+        entries
+            .remove(key)
+            .filter(|entry| entry.since.elapsed() < self.ttl)
+            .map(|entry| entry.text)
+    }
+
+    /// Forgets every query `user_id` handed over; `false` when there were
+    /// none.
+    pub fn clear(&self, user_id: u64) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.len();
+        entries.retain(|_, entry| entry.user_id != user_id);
+        entries.len() < before
+    }
+}
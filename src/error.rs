@@ -0,0 +1,118 @@
+use crate::types::LanguageCode;
+use thiserror::Error;
+
+/// Why a translation could not be produced.
+#[derive(Debug, Clone, Error)]
+pub enum TranslationError {
+    #[error("Translation provider timed out")]
+    Timeout,
+    #[error("{}", rate_limited_message(*retry_after_secs))]
+    RateLimited { retry_after_secs: Option<u64> },
+    #[error("Translation service temporarily unavailable, try again later")]
+    Unavailable,
+    #[error("Too many translations in progress, try again shortly")]
+    Busy,
+    #[error("Translation provider rejected the credentials")]
+    Auth,
+    #[error("Translation provider declined the request: {0}")]
+    ProviderDeclined(String),
+    #[error("Translation provider failed ({status}): {message}")]
+    Provider { status: u16, message: String },
+    #[error("Translation provider sent an unusable response: {0}")]
+    Malformed(String),
+    #[error("Translation provider response exceeded {0} bytes")]
+    ResponseTooLarge(usize),
+    #[error("Translation provider returned an empty translation")]
+    EmptyTranslation,
+    #[error("Romanization is not available for {0}")]
+    RomanizationUnavailable(LanguageCode),
+    #[error("Could not reach the translation provider: {0}")]
+    Network(String),
+    #[error("{0}")]
+    Other(String),
+}
+
+/// How much of a failed translation's cause users are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorDetail {
+    /// Only that it failed.
+    Generic,
+    /// What kind of failure it was, without provider status codes or text.
+    Category,
+    /// The error as logged.
+    Full,
+}
+
+impl std::str::FromStr for ErrorDetail {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "generic" => Ok(ErrorDetail::Generic),
+            "category" => Ok(ErrorDetail::Category),
+            "full" => Ok(ErrorDetail::Full),
+            other => Err(format!("unknown error detail {:?}", other)),
+        }
+    }
+}
+
+/// Why raw input could not be turned into a translation request.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ParseError {
+    #[error("Nothing to translate")]
+    Empty,
+    #[error("Unsupported source language: {0}")]
+    UnsupportedSource(String),
+}
+
+fn rate_limited_message(retry_after_secs: Option<u64>) -> String {
+    match retry_after_secs {
+        Some(secs) => format!(
+            "Translation provider is busy, try again in {} seconds",
+            secs.max(1)
+        ),
+        None => "Translation provider is busy, try again shortly".to_string(),
+    }
+}
+
+impl TranslationError {
+    /// Whether the same request might succeed if sent again.
+    pub fn is_retriable(&self) -> bool {
+        match self {
+            TranslationError::Timeout
+            | TranslationError::RateLimited { .. }
+            | TranslationError::Busy
+            | TranslationError::Network(_) => true,
+            TranslationError::Provider { status, .. } => *status >= 500,
+            _ => false,
+        }
+    }
+
+    /// Description for users at the given level of detail.
+    pub fn user_message(&self, detail: ErrorDetail) -> String {
+        match detail {
+            ErrorDetail::Generic => "try again later".to_string(),
+            ErrorDetail::Full => self.to_string(),
+            ErrorDetail::Category => match self {
+                TranslationError::Auth
+                | TranslationError::Provider { .. }
+                | TranslationError::Malformed(_)
+                | TranslationError::ResponseTooLarge(_)
+                | TranslationError::Other(_) => "Translation provider failed".to_string(),
+                TranslationError::ProviderDeclined(_) => {
+                    "Translation provider declined the request".to_string()
+                }
+                TranslationError::Network(_) => {
+                    "Could not reach the translation provider".to_string()
+                }
+                // These say nothing about the provider's internals.
+                TranslationError::Timeout
+                | TranslationError::RateLimited { .. }
+                | TranslationError::Unavailable
+                | TranslationError::Busy
+                | TranslationError::EmptyTranslation
+                | TranslationError::RomanizationUnavailable(_) => self.to_string(),
+            },
+        }
+    }
+}
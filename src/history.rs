@@ -0,0 +1,79 @@
+use crate::types::LanguageCode;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// One translation a user asked for.
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub at: Instant,
+    pub text: String,
+    pub source_lang: LanguageCode,
+    pub target_lang: LanguageCode,
+    pub translation: String,
+}
+
+/// Each user's most recent translations, newest last, for `/history`.
+/// Kept in memory only, so history does not survive a restart. A `limit`
+/// of 0 records nothing.
+pub struct HistoryStore {
+    limit: usize,
+    entries: Mutex<HashMap<u64, VecDeque<HistoryEntry>>>,
+}
+
+impl HistoryStore {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.limit > 0
+    }
+
+    pub fn record(
+        &self,
+        user_id: u64,
+        text: &str,
+        source_lang: LanguageCode,
+        target_lang: LanguageCode,
+        translation: &str,
+    ) {
+        if !self.is_enabled() {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        let history = entries.entry(user_id).or_default();
+        if history.len() >= self.limit {
+            history.pop_front();
+        }
+        history.push_back(HistoryEntry {
+            at: Instant::now(),
+            text: text.to_string(),
+            source_lang,
+            target_lang,
+            translation: translation.to_string(),
+        });
+    }
+
+    /// The user's entries, newest first.
+    pub fn recent(&self, user_id: u64) -> Vec<HistoryEntry> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(&user_id)
+            .map(|history| history.iter().rev().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Forgets the user's history; returns whether there was any.
+    pub fn clear(&self, user_id: u64) -> bool {
+        self.entries
+            .lock()
+            .unwrap()
+            .remove(&user_id)
+            .is_some_and(|history| !history.is_empty())
+    }
+}
@@ -0,0 +1,279 @@
+//! Strings the bot itself shows to users, per UI language.
+
+/// Language of the bot's own messages (not of translations).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UiLanguage {
+    En,
+    Zh,
+}
+
+impl std::str::FromStr for UiLanguage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "en" => Ok(UiLanguage::En),
+            "zh" => Ok(UiLanguage::Zh),
+            other => Err(format!("unsupported UI language {:?}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Msg {
+    Start,
+    NothingToForget,
+    AuditLogRetained,
+    TranslatePrompt,
+    TranslateAllUsage,
+    RomanizeUsage,
+    ExplainUsage,
+    ExplainDisabled,
+    ChatBusy,
+    BotBusyTitle,
+    BotBusy,
+    OpenInChat,
+    /// `{0}`: the direction header of the translation above.
+    DirectionUnsure,
+    SwapDirection,
+    SwapSourceMissing,
+    AlternativesLabel,
+    ExplanationLabel,
+    Unchanged,
+    /// `{0}`: crate version, `{1}`: git commit.
+    Version,
+    /// `{0}`: provider flavor, `{1}`: model.
+    VersionProvider,
+    ProviderHealthy,
+    ProviderUnhealthy,
+    ProviderUnchecked,
+    CouldNotUnderstand,
+    NoTextInImage,
+    OcrFailed,
+    /// `{0}`: error detail.
+    TranslationFailed,
+    TranslationFailedTitle,
+    StillTranslatingTitle,
+    StillTranslating,
+    /// `{0}`: romanization scheme, e.g. "Pinyin".
+    ShowRomanization,
+    RomanizationExpired,
+    HelpTitle,
+    HelpDescription,
+    /// `{0}`: segment delimiter, `{1}`/`{2}`: default direction.
+    HelpBody,
+    /// `{1}`/`{2}`: the locked direction.
+    HelpDescriptionLocked,
+    /// Like `HelpBody`, for deployments with `lock_direction`.
+    HelpBodyLocked,
+    CommandStart,
+    CommandTr,
+    CommandTrAll,
+    CommandRomanize,
+    CommandExplain,
+    CommandVersion,
+    CommandStatus,
+    CommandHistory,
+    CommandClearHistory,
+    CommandVerbosity,
+    CommandForgetMe,
+    CommandReload,
+    Reloaded,
+    /// `{0}`: why the new configuration was rejected.
+    ReloadFailed,
+    AdminOnly,
+    Forgotten,
+    /// `{0}`: the user's verbosity.
+    VerbosityCurrent,
+    /// `{0}`: the new verbosity.
+    VerbositySet,
+    HistoryDisabled,
+    HistoryEmpty,
+    HistoryCleared,
+    /// `{0}`: page number, `{1}`: page count.
+    HistoryPage,
+}
+
+/// Looks up `msg` in `lang`, falling back to English for missing entries.
+pub fn text(lang: UiLanguage, msg: Msg) -> &'static str {
+    let localized = match lang {
+        UiLanguage::En => None,
+        UiLanguage::Zh => zh(msg),
+    };
+    localized.unwrap_or_else(|| en(msg))
+}
+
+/// Replaces `{0}`, `{1}`, … in `template` with `args`.
+pub fn fill(template: &str, args: &[&str]) -> String {
+    args.iter()
+        .enumerate()
+        .fold(template.to_string(), |acc, (index, arg)| {
+            acc.replace(&format!("{{{}}}", index), arg)
+        })
+}
+
+fn en(msg: Msg) -> &'static str {
+    match msg {
+        Msg::Start => {
+            "👋 Inline Translation Bot\nType @OukaroSUtslt_bot followed by text anywhere to translate between English and Chinese.\nYou can also send me text directly here!"
+        }
+        Msg::NothingToForget => "Nothing to forget: no history or settings of yours are stored.",
+        Msg::AuditLogRetained => {
+            "Records of the translations you requested are kept in the operator's audit log and are not deleted."
+        }
+        Msg::TranslatePrompt => "Reply with the text to translate.",
+        Msg::TranslateAllUsage => "Reply to a message or album with /tr_all to translate it.",
+        Msg::RomanizeUsage => "Send /romanize followed by Chinese text to get its pinyin.",
+        Msg::ExplainUsage => {
+            "Send /explain followed by an idiom or phrase to get its translation and meaning."
+        }
+        Msg::ExplainDisabled => "Explanations are not enabled on this bot.",
+        Msg::ChatBusy => "⏳ Still translating earlier messages here, try again in a moment.",
+        Msg::BotBusyTitle => "⏳ Too busy",
+        Msg::BotBusy => "⏳ Too many requests right now, try again in a moment.",
+        Msg::OpenInChat => "Open chat for full translation",
+        Msg::DirectionUnsure => "Not sure you meant {0}.",
+        Msg::SwapDirection => "↔ Translate the other way",
+        Msg::SwapSourceMissing => "The original message is no longer available to translate.",
+        Msg::AlternativesLabel => "Alternatives:",
+        Msg::ExplanationLabel => "Meaning:",
+        Msg::Unchanged => {
+            "ℹ️ No translation needed: the text seems to be in the target language already."
+        }
+        Msg::Version => "Version {0} (commit {1})",
+        Msg::VersionProvider => "Provider: {0}, model: {1}",
+        Msg::ProviderHealthy => "✅ The translation provider passed its latest health check.",
+        Msg::ProviderUnhealthy => "⚠️ The translation provider failed its latest health check.",
+        Msg::ProviderUnchecked => "Provider health checks are turned off.",
+        Msg::CouldNotUnderstand => "Could not understand the input. Please try again.",
+        Msg::NoTextInImage => "No text found in the image.",
+        Msg::OcrFailed => "⚠️ Could not read text from the image.",
+        Msg::TranslationFailed => "⚠️ Translation failed: {0}",
+        Msg::TranslationFailedTitle => "Translation failed",
+        Msg::StillTranslatingTitle => "⏳ Still translating…",
+        Msg::StillTranslating => "This one is taking a while. Type the query again in a moment.",
+        Msg::ShowRomanization => "Show {0}",
+        Msg::RomanizationExpired => "This button has expired.",
+        Msg::HelpTitle => "How to translate",
+        Msg::HelpDescription => "Prefix with en>zh or zh>en, and use | to split sentences.",
+        Msg::HelpBody => {
+            "Type something after the bot handle. Use \"{0}\" to separate segments when you want grouped translations (topic | detail).\n\
+            Examples:\n\
+            • @yourbot en>zh: sustainability roadmap | 2025 goals\n\
+            • @yourbot zh>en: 开会推迟到几点?\n\
+            Defaults to {1}→{2} when not detectable."
+        }
+        Msg::HelpDescriptionLocked => "Everything is translated {1}→{2}. Use | to split sentences.",
+        Msg::HelpBodyLocked => {
+            "Type something after the bot handle; it is always translated {1}→{2}. Use \"{0}\" to separate segments when you want grouped translations (topic | detail).\n\
+            Example:\n\
+            • @yourbot sustainability roadmap | 2025 goals"
+        }
+        Msg::CommandStart => "Show the welcome message",
+        Msg::CommandTr => "Translate text, or the reply to this prompt",
+        Msg::CommandTrAll => "Translate the replied-to message or album",
+        Msg::CommandRomanize => "Show the pinyin of Chinese text",
+        Msg::CommandExplain => "Translate and explain an idiom",
+        Msg::CommandVersion => "Show the bot version",
+        Msg::CommandStatus => "Show whether the translation provider is answering",
+        Msg::CommandHistory => "Show your recent translations",
+        Msg::CommandClearHistory => "Delete your translation history",
+        Msg::CommandVerbosity => "Choose terse, normal or rich output",
+        Msg::CommandForgetMe => "Delete data stored about you",
+        Msg::CommandReload => "Reload translator settings (admins only)",
+        Msg::Reloaded => {
+            "Translator settings reloaded. The bot token, endpoint and bot settings change only after a restart."
+        }
+        Msg::ReloadFailed => "⚠️ Configuration not reloaded: {0}",
+        Msg::AdminOnly => "Only bot admins can do that.",
+        Msg::Forgotten => {
+            "Done: your translation history, settings and pending requests have been deleted."
+        }
+        Msg::VerbosityCurrent => {
+            "Output verbosity: {0}. Change it with /verbosity terse, /verbosity normal or /verbosity rich."
+        }
+        Msg::VerbositySet => "Output verbosity set to {0}.",
+        Msg::HistoryDisabled => "Translation history is not enabled on this bot.",
+        Msg::HistoryEmpty => "You have no translation history yet.",
+        Msg::HistoryCleared => "Your translation history has been cleared.",
+        Msg::HistoryPage => "Recent translations, page {0} of {1} (/history N for more):",
+    }
+}
+
+fn zh(msg: Msg) -> Option<&'static str> {
+    Some(match msg {
+        Msg::Start => {
+            "👋 内联翻译机器人\n在任意聊天中输入 @OukaroSUtslt_bot 加上文字,即可在英文和中文之间互译。\n也可以直接在这里给我发消息!"
+        }
+        Msg::NothingToForget => "无需删除:没有保存你的翻译记录或设置。",
+        Msg::AuditLogRetained => "你请求过的翻译记录保存在运营者的审计日志中,不会被删除。",
+        Msg::TranslatePrompt => "请回复要翻译的文字。",
+        Msg::TranslateAllUsage => "回复一条消息或相册并发送 /tr_all 即可翻译。",
+        Msg::RomanizeUsage => "发送 /romanize 加中文文字即可获得拼音。",
+        Msg::ExplainUsage => "发送 /explain 加成语或短语即可获得翻译及其含义。",
+        Msg::ExplainDisabled => "本机器人未启用释义功能。",
+        Msg::ChatBusy => "⏳ 正在翻译本聊天中之前的消息,请稍后再试。",
+        Msg::BotBusyTitle => "⏳ 服务繁忙",
+        Msg::BotBusy => "⏳ 当前请求过多,请稍后再试。",
+        Msg::OpenInChat => "在私聊中查看完整翻译",
+        Msg::DirectionUnsure => "不确定你是否想要 {0}。",
+        Msg::SwapDirection => "↔ 反向翻译",
+        Msg::SwapSourceMissing => "原消息已无法获取，无法反向翻译。",
+        Msg::AlternativesLabel => "其他译法:",
+        Msg::ExplanationLabel => "释义:",
+        Msg::Unchanged => "ℹ️ 无需翻译:文本似乎已是目标语言。",
+        Msg::Version => "版本 {0}(提交 {1})",
+        Msg::VersionProvider => "服务商:{0},模型:{1}",
+        Msg::ProviderHealthy => "✅ 翻译服务通过了最近一次健康检查。",
+        Msg::ProviderUnhealthy => "⚠️ 翻译服务未通过最近一次健康检查。",
+        Msg::ProviderUnchecked => "翻译服务健康检查未开启。",
+        Msg::CouldNotUnderstand => "无法理解输入内容,请重试。",
+        Msg::NoTextInImage => "图片中没有找到文字。",
+        Msg::OcrFailed => "⚠️ 无法识别图片中的文字。",
+        Msg::TranslationFailed => "⚠️ 翻译失败:{0}",
+        Msg::TranslationFailedTitle => "翻译失败",
+        Msg::StillTranslatingTitle => "⏳ 仍在翻译…",
+        Msg::StillTranslating => "这次翻译需要多一点时间,请稍后重新输入。",
+        Msg::ShowRomanization => "显示{0}",
+        Msg::RomanizationExpired => "此按钮已过期。",
+        Msg::HelpTitle => "使用方法",
+        Msg::HelpDescription => "以 en>zh 或 zh>en 开头,用 | 分隔句子。",
+        Msg::HelpBody => {
+            "在机器人用户名后输入文字。需要分组翻译时用 \"{0}\" 分隔各段(主题 | 细节)。\n\
+            示例:\n\
+            • @yourbot en>zh: sustainability roadmap | 2025 goals\n\
+            • @yourbot zh>en: 开会推迟到几点?\n\
+            无法识别语言时默认 {1}→{2}。"
+        }
+        Msg::HelpDescriptionLocked => "所有内容均按 {1}→{2} 翻译,用 | 分隔句子。",
+        Msg::HelpBodyLocked => {
+            "在机器人用户名后输入文字,始终按 {1}→{2} 翻译。需要分组翻译时用 \"{0}\" 分隔各段(主题 | 细节)。\n\
+            示例:\n\
+            • @yourbot sustainability roadmap | 2025 goals"
+        }
+        Msg::CommandStart => "显示欢迎信息",
+        Msg::CommandTr => "翻译文字,或翻译对提示的回复",
+        Msg::CommandTrAll => "翻译所回复的消息或相册",
+        Msg::CommandRomanize => "显示中文的拼音",
+        Msg::CommandExplain => "翻译并解释成语",
+        Msg::CommandVersion => "显示机器人版本",
+        Msg::CommandStatus => "显示翻译服务是否正常",
+        Msg::CommandHistory => "显示最近的翻译",
+        Msg::CommandClearHistory => "删除翻译记录",
+        Msg::CommandVerbosity => "选择简洁、普通或详细输出",
+        Msg::CommandForgetMe => "删除关于你的数据",
+        Msg::CommandReload => "重新加载翻译设置(仅限管理员)",
+        Msg::Reloaded => "翻译设置已重新加载。机器人令牌、接口地址和机器人设置需重启后生效。",
+        Msg::ReloadFailed => "⚠️ 配置未重新加载:{0}",
+        Msg::AdminOnly => "只有机器人管理员可以执行此操作。",
+        Msg::Forgotten => "完成:你的翻译记录、设置和待处理请求已删除。",
+        Msg::VerbosityCurrent => {
+            "输出详细程度:{0}。可用 /verbosity terse、/verbosity normal 或 /verbosity rich 更改。"
+        }
+        Msg::VerbositySet => "输出详细程度已设为 {0}。",
+        Msg::HistoryDisabled => "此机器人未启用翻译记录。",
+        Msg::HistoryEmpty => "你还没有翻译记录。",
+        Msg::HistoryCleared => "你的翻译记录已清除。",
+        Msg::HistoryPage => "最近的翻译,第 {0}/{1} 页(发送 /history N 查看更多):",
+    })
+}
@@ -0,0 +1,575 @@
+use super::core::{SEGMENT_DELIMITER, format_segments_for_display, truncate};
+use crate::config::{Config, InlineResult};
+use crate::history::HistoryEntry;
+use crate::i18n::{self, Msg, UiLanguage};
+use crate::telegram::escape_markdown_v2;
+use crate::types::{ParsedInlineQuery, TranslationResult};
+use sha2::{Digest, Sha256};
+use std::time::Instant;
+use teloxide::types::{
+    InlineKeyboardButton, InlineKeyboardMarkup, InlineQueryResult, InlineQueryResultArticle,
+    InputMessageContent, InputMessageContentText, ParseMode,
+};
+use uuid::Uuid;
+
+const DEFAULT_ALTERNATIVES: usize = 3;
+/// Longest input, in words, that gets a dictionary link.
+const DICTIONARY_LINK_MAX_WORDS: usize = 3;
+/// Characters of the source shown when `inline_include_source` is on.
+const SOURCE_PREVIEW_CHARS: usize = 200;
+/// Callback data prefix of the "Show pinyin" button; the store key follows.
+pub const ROMANIZATION_CALLBACK_PREFIX: &str = "rom:";
+
+/// Which of our inline results a result id belongs to, encoded as an id
+/// prefix so chosen-result feedback can be attributed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArticleKind {
+    Primary,
+    Romanized,
+    Alternatives,
+    Explanation,
+    Dictionary,
+    Help,
+    Error,
+    History,
+    Pending,
+    Busy,
+}
+
+impl ArticleKind {
+    const ALL: [ArticleKind; 10] = [
+        ArticleKind::Primary,
+        ArticleKind::Romanized,
+        ArticleKind::Alternatives,
+        ArticleKind::Explanation,
+        ArticleKind::Dictionary,
+        ArticleKind::Help,
+        ArticleKind::Error,
+        ArticleKind::History,
+        ArticleKind::Pending,
+        ArticleKind::Busy,
+    ];
+
+    fn prefix(self) -> &'static str {
+        match self {
+            ArticleKind::Primary => "p",
+            ArticleKind::Romanized => "r",
+            ArticleKind::Alternatives => "a",
+            ArticleKind::Explanation => "x",
+            ArticleKind::Dictionary => "d",
+            ArticleKind::Help => "h",
+            ArticleKind::Error => "e",
+            ArticleKind::History => "y",
+            ArticleKind::Pending => "w",
+            ArticleKind::Busy => "b",
+        }
+    }
+
+    /// A result id of this kind, e.g. "p:3f2b…": derived from `seed` when
+    /// given, so the same input always gets the same id, otherwise random.
+    fn result_id(self, seed: Option<u64>) -> String {
+        match seed {
+            Some(seed) => format!("{}:{:016x}", self.prefix(), seed),
+            None => format!("{}:{}", self.prefix(), Uuid::new_v4()),
+        }
+    }
+
+    /// Recovers the kind from one of our result ids.
+    pub fn from_result_id(id: &str) -> Option<Self> {
+        let (prefix, _) = id.split_once(':')?;
+        Self::ALL.into_iter().find(|kind| kind.prefix() == prefix)
+    }
+}
+
+/// Renders a translation as inline results: the primary text, the
+/// romanization, alternatives and explanation when the provider supplied
+/// them, and a dictionary link, in the order of `inline_result_order`.
+///
+/// With a `romanization_key`, the primary result carries a button that looks
+/// the romanization up under that key when pressed.
+pub fn build_translation_articles(
+    parsed: &ParsedInlineQuery,
+    translation: &TranslationResult,
+    config: &Config,
+    romanization_key: Option<&str>,
+) -> Vec<InlineQueryResult> {
+    let header = if parsed.romanize_only {
+        format!(
+            "{} {}",
+            config.header_prefix,
+            parsed.source_lang.to_string().to_uppercase()
+        )
+        .trim()
+        .to_string()
+    } else {
+        config.direction_header(parsed.source_lang, parsed.target_lang)
+    };
+    let primary_display = format_segments_for_display(&translation.primary_text);
+    let source = config
+        .inline_include_source
+        .then(|| truncate(&parsed.text, SOURCE_PREVIEW_CHARS));
+
+    let seed = config.deterministic_ids.then(|| {
+        stable_hash(&[
+            &parsed.source_lang.to_string(),
+            &parsed.target_lang.to_string(),
+            &parsed.text,
+            &format!("{:?}", parsed.alternative_count),
+            &parsed.dictionary.to_string(),
+            &parsed.romanize_only.to_string(),
+            &parsed.explain.to_string(),
+            &format!("{:?}", parsed.context),
+        ])
+    });
+
+    // Primary result, with the dictionary details of a single-word lookup;
+    // romanize-only queries have no translation to show
+    let mut primary = None;
+    if !parsed.romanize_only {
+        let id = ArticleKind::Primary.result_id(seed);
+        let mut content = primary_display.clone();
+        if let Some(pos) = &translation.part_of_speech {
+            content = format!("{} ({})", content, pos);
+        }
+        if let Some(example) = &translation.example {
+            content = format!("{}\n“{}”", content, example);
+        }
+        let article = InlineQueryResultArticle::new(
+            id,
+            format!("{} · Primary", header),
+            message_content(&header, &content, source.as_deref()),
+        )
+        .description(if translation.unchanged {
+            i18n::text(config.ui_language, Msg::Unchanged).to_string()
+        } else {
+            truncate(&primary_display, 80)
+        });
+        let article = match (romanization_key, parsed.target_lang.romanization_scheme()) {
+            (Some(key), Some(scheme)) if translation.romanized_text.is_some() => {
+                let label = i18n::fill(
+                    i18n::text(config.ui_language, Msg::ShowRomanization),
+                    &[scheme],
+                );
+                let button = InlineKeyboardButton::callback(
+                    label,
+                    format!("{}{}", ROMANIZATION_CALLBACK_PREFIX, key),
+                );
+                article.reply_markup(InlineKeyboardMarkup::new([[button]]))
+            }
+            _ => article,
+        };
+        primary = Some(InlineQueryResult::from(article));
+    }
+
+    // Romanized result, labelled with the target's scheme (e.g. "Pinyin")
+    let mut romanized = None;
+    if let Some((text, scheme)) = translation
+        .romanized_text
+        .as_ref()
+        .zip(parsed.target_lang.romanization_scheme())
+    {
+        let romanized_display = format_segments_for_display(text);
+        let id = ArticleKind::Romanized.result_id(seed);
+        let content = romanized_display.clone();
+        let article = InlineQueryResultArticle::new(
+            id,
+            if translation.romanized_is_fallback {
+                format!("{} · {} (approx.)", header, scheme)
+            } else {
+                format!("{} · {}", header, scheme)
+            },
+            message_content(&header, &content, source.as_deref()),
+        )
+        .description(truncate(&romanized_display, 80));
+        romanized = Some(InlineQueryResult::from(article));
+    }
+
+    // Alternatives
+    let mut alternatives = None;
+    if !translation.alternate_texts.is_empty() && parsed.alternative_count != Some(0) {
+        let alt_samples: Vec<String> = translation
+            .alternate_texts
+            .iter()
+            .take(parsed.alternative_count.unwrap_or(DEFAULT_ALTERNATIVES))
+            .map(|s| format_segments_for_display(s))
+            .collect();
+        let bullets = alt_samples
+            .iter()
+            .map(|line| format!("• {}", line))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let id = ArticleKind::Alternatives.result_id(seed);
+        let content = bullets;
+        let article = InlineQueryResultArticle::new(
+            id,
+            format!("{} · Alternatives", header),
+            message_content(&header, &content, source.as_deref()),
+        )
+        .description(truncate(&alt_samples[0], 80));
+        alternatives = Some(InlineQueryResult::from(article));
+    }
+
+    // Explanation of idioms and cultural meaning, for explain queries
+    let mut explanation = None;
+    if let Some(text) = &translation.explanation {
+        let id = ArticleKind::Explanation.result_id(seed);
+        let content = format!("{}\n\n{}", primary_display, text);
+        let article = InlineQueryResultArticle::new(
+            id,
+            format!("{} · Explanation", header),
+            message_content(&header, &content, source.as_deref()),
+        )
+        .description(truncate(text, 80));
+        explanation = Some(InlineQueryResult::from(article));
+    }
+
+    // Dictionary link for words and short phrases
+    let mut dictionary = None;
+    if let Some(template) = &config.dictionary_url_template
+        && !parsed.romanize_only
+        && !parsed.text.contains(SEGMENT_DELIMITER)
+        && parsed.text.split_whitespace().count() <= DICTIONARY_LINK_MAX_WORDS
+    {
+        let url = template.replace("{word}", &percent_encode(&parsed.text));
+        let id = ArticleKind::Dictionary.result_id(seed);
+        let article = InlineQueryResultArticle::new(
+            id,
+            format!("{} · Dictionary", header),
+            InputMessageContent::Text(InputMessageContentText::new(format!(
+                "🔎 {}\n{}",
+                parsed.text, url
+            ))),
+        )
+        .description(url);
+        dictionary = Some(InlineQueryResult::from(article));
+    }
+
+    config
+        .inline_result_order
+        .iter()
+        .filter_map(|kind| match kind {
+            InlineResult::Primary => primary.take(),
+            InlineResult::Romanized => romanized.take(),
+            InlineResult::Alternatives => alternatives.take(),
+            InlineResult::Explanation => explanation.take(),
+            InlineResult::Dictionary => dictionary.take(),
+        })
+        .collect()
+}
+
+/// The history entry for each translation in `results`, keyed by result id:
+/// the text that article sends, so picking the alternatives or the
+/// romanization records those rather than the primary translation.
+pub fn chosen_entries(
+    results: &[InlineQueryResult],
+    parsed: &ParsedInlineQuery,
+    translation: &TranslationResult,
+) -> Vec<(String, HistoryEntry)> {
+    results
+        .iter()
+        .filter_map(|result| {
+            let InlineQueryResult::Article(article) = result else {
+                return None;
+            };
+            let sent = match ArticleKind::from_result_id(&article.id)? {
+                ArticleKind::Primary | ArticleKind::Explanation => translation.primary_text.clone(),
+                ArticleKind::Romanized => translation.romanized_text.clone()?,
+                ArticleKind::Alternatives => translation
+                    .alternate_texts
+                    .iter()
+                    .take(parsed.alternative_count.unwrap_or(DEFAULT_ALTERNATIVES))
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                _ => return None,
+            };
+            let entry = HistoryEntry {
+                at: Instant::now(),
+                text: parsed.text.clone(),
+                source_lang: parsed.source_lang,
+                target_lang: parsed.target_lang,
+                translation: sent,
+            };
+            Some((article.id.clone(), entry))
+        })
+        .collect()
+}
+
+/// First 8 bytes of the SHA-256 of `parts`, each length-prefixed so
+/// ("ab", "c") and ("a", "bc") differ. Unlike `DefaultHasher` this does not
+/// change between Rust releases, so ids survive a toolchain upgrade.
+fn stable_hash(parts: &[&str]) -> u64 {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part.as_bytes());
+    }
+    let digest = hasher.finalize();
+    u64::from_be_bytes(digest[..8].try_into().unwrap())
+}
+
+/// Percent-encodes everything but RFC 3986 unreserved characters.
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Message sent for a translation result: the header and `body`, with
+/// `source` above them as a MarkdownV2 spoiler when given.
+fn message_content(header: &str, body: &str, source: Option<&str>) -> InputMessageContent {
+    let text = format!("{}\n{}", header, body);
+    let content = match source {
+        Some(source) => InputMessageContentText::new(format!(
+            "||{}||\n{}",
+            escape_markdown_v2(source),
+            escape_markdown_v2(&text)
+        ))
+        .parse_mode(ParseMode::MarkdownV2),
+        None => InputMessageContentText::new(text),
+    };
+    InputMessageContent::Text(content)
+}
+
+/// Past translations offered for an empty inline query, newest first, so
+/// they can be sent again without retyping.
+pub fn build_history_articles(entries: &[HistoryEntry], config: &Config) -> Vec<InlineQueryResult> {
+    entries
+        .iter()
+        .take(config.max_inline_results)
+        .enumerate()
+        .map(|(index, entry)| {
+            let header = config.direction_header(entry.source_lang, entry.target_lang);
+            // The position keeps ids unique when the same text was translated
+            // twice; Telegram rejects an answer with duplicate result ids
+            let article = InlineQueryResultArticle::new(
+                ArticleKind::History.result_id(config.deterministic_ids.then(|| {
+                    stable_hash(&[
+                        &index.to_string(),
+                        &entry.source_lang.to_string(),
+                        &entry.target_lang.to_string(),
+                        &entry.text,
+                        &entry.translation,
+                    ])
+                })),
+                format!("{} · {}", header, truncate(&entry.text, 40)),
+                message_content(&header, &entry.translation, None),
+            )
+            .description(truncate(&entry.translation, 80));
+            InlineQueryResult::from(article)
+        })
+        .collect()
+}
+
+/// Result shown when the inline query is empty or could not be parsed.
+pub fn build_help_article(config: &Config) -> InlineQueryResult {
+    let lang = config.ui_language;
+    let (body, description) = if config.lock_direction {
+        (Msg::HelpBodyLocked, Msg::HelpDescriptionLocked)
+    } else {
+        (Msg::HelpBody, Msg::HelpDescription)
+    };
+    let args = [
+        SEGMENT_DELIMITER,
+        &config.default_source_lang.to_string(),
+        &config.default_target_lang.to_string(),
+    ];
+    let message = i18n::fill(i18n::text(lang, body), &args);
+
+    let id = ArticleKind::Help.result_id(config.deterministic_ids.then_some(0));
+    let article = InlineQueryResultArticle::new(
+        id,
+        i18n::text(lang, Msg::HelpTitle),
+        InputMessageContent::Text(InputMessageContentText::new(message)),
+    )
+    .description(i18n::fill(i18n::text(lang, description), &args));
+
+    InlineQueryResult::from(article)
+}
+
+/// Result shown when the input was rejected or the provider call failed.
+pub fn build_error_article(error: &dyn std::fmt::Display, lang: UiLanguage) -> InlineQueryResult {
+    let message = error.to_string();
+    let id = ArticleKind::Error.result_id(None);
+    let content = i18n::fill(i18n::text(lang, Msg::TranslationFailed), &[&message]);
+    let article = InlineQueryResultArticle::new(
+        id,
+        i18n::text(lang, Msg::TranslationFailedTitle),
+        InputMessageContent::Text(InputMessageContentText::new(content)),
+    )
+    .description(message);
+
+    InlineQueryResult::from(article)
+}
+
+/// Result shown when the translation did not finish before the inline
+/// deadline.
+pub fn build_pending_article(lang: UiLanguage) -> InlineQueryResult {
+    let id = ArticleKind::Pending.result_id(None);
+    let article = InlineQueryResultArticle::new(
+        id,
+        i18n::text(lang, Msg::StillTranslatingTitle),
+        InputMessageContent::Text(InputMessageContentText::new(i18n::text(
+            lang,
+            Msg::StillTranslating,
+        ))),
+    )
+    .description(i18n::text(lang, Msg::StillTranslating));
+
+    InlineQueryResult::from(article)
+}
+
+/// Result shown when the bot is at `max_concurrent_updates`.
+pub fn build_busy_article(lang: UiLanguage) -> InlineQueryResult {
+    let id = ArticleKind::Busy.result_id(None);
+    let article = InlineQueryResultArticle::new(
+        id,
+        i18n::text(lang, Msg::BotBusyTitle),
+        InputMessageContent::Text(InputMessageContentText::new(i18n::text(lang, Msg::BotBusy))),
+    )
+    .description(i18n::text(lang, Msg::BotBusy));
+
+    InlineQueryResult::from(article)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::LanguageCode;
+
+    fn entry(text: &str, translation: &str) -> HistoryEntry {
+        HistoryEntry {
+            at: Instant::now(),
+            text: text.to_string(),
+            source_lang: LanguageCode::En,
+            target_lang: LanguageCode::Zh,
+            translation: translation.to_string(),
+        }
+    }
+
+    fn ids(results: &[InlineQueryResult]) -> Vec<String> {
+        results
+            .iter()
+            .map(|result| match result {
+                InlineQueryResult::Article(article) => article.id.clone(),
+                other => panic!("unexpected result {:?}", other),
+            })
+            .collect()
+    }
+
+    fn good_morning() -> (ParsedInlineQuery, TranslationResult) {
+        let parsed = ParsedInlineQuery {
+            text: "good morning".to_string(),
+            source_lang: LanguageCode::En,
+            target_lang: LanguageCode::Zh,
+            alternative_count: Some(2),
+            dictionary: false,
+            romanize_only: false,
+            explain: false,
+            context: None,
+            detection_confidence: None,
+        };
+        let translation = TranslationResult {
+            primary_text: "早上好".to_string(),
+            alternate_texts: vec!["早安".to_string(), "上午好".to_string(), "晨安".to_string()],
+            romanized_text: Some("zǎo shang hǎo".to_string()),
+            provider_latency_ms: 0,
+            romanized_is_fallback: false,
+            truncated: false,
+            unchanged: false,
+            part_of_speech: None,
+            example: None,
+            explanation: None,
+        };
+        (parsed, translation)
+    }
+
+    #[test]
+    fn repeated_history_entries_get_distinct_ids() {
+        let config = Config::for_tests(&[("DETERMINISTIC_IDS", "true")]);
+        let entries = [entry("hello", "你好"), entry("hello", "你好")];
+        let ids = ids(&build_history_articles(&entries, &config));
+        assert_eq!(ids.len(), 2);
+        assert_ne!(ids[0], ids[1]);
+    }
+
+    #[test]
+    fn history_ids_are_stable() {
+        let config = Config::for_tests(&[("DETERMINISTIC_IDS", "true")]);
+        let entries = [entry("hello", "你好")];
+        assert_eq!(
+            ids(&build_history_articles(&entries, &config)),
+            ["y:f9a0af619b316d8b"]
+        );
+    }
+
+    #[test]
+    fn stable_hash_separates_fields() {
+        assert_ne!(stable_hash(&["ab", "c"]), stable_hash(&["a", "bc"]));
+    }
+
+    #[test]
+    fn locked_help_does_not_mention_prefixes() {
+        let locked = Config::for_tests(&[("LOCK_DIRECTION", "true")]);
+        let InlineQueryResult::Article(article) = build_help_article(&locked) else {
+            panic!("help is an article");
+        };
+        assert_eq!(
+            article.description.as_deref(),
+            Some("Everything is translated en→zh. Use | to split sentences.")
+        );
+    }
+
+    #[test]
+    fn results_follow_the_configured_order() {
+        let config =
+            Config::for_tests(&[("INLINE_RESULT_ORDER", "alternatives, romanized, bogus")]);
+        let (parsed, translation) = good_morning();
+        let kinds: Vec<_> = ids(&build_translation_articles(
+            &parsed,
+            &translation,
+            &config,
+            None,
+        ))
+        .iter()
+        .map(|id| ArticleKind::from_result_id(id).unwrap())
+        .collect();
+        // The unknown name is skipped and the unlisted primary follows.
+        assert_eq!(
+            kinds,
+            [
+                ArticleKind::Alternatives,
+                ArticleKind::Romanized,
+                ArticleKind::Primary,
+            ]
+        );
+    }
+
+    #[test]
+    fn chosen_entries_hold_what_each_article_sends() {
+        let config = Config::for_tests(&[]);
+        let (parsed, translation) = good_morning();
+        let results = build_translation_articles(&parsed, &translation, &config, None);
+
+        let chosen: Vec<_> = chosen_entries(&results, &parsed, &translation)
+            .into_iter()
+            .map(|(id, entry)| {
+                assert_eq!(entry.text, "good morning");
+                (ArticleKind::from_result_id(&id).unwrap(), entry.translation)
+            })
+            .collect();
+        assert_eq!(
+            chosen,
+            [
+                (ArticleKind::Primary, "早上好".to_string()),
+                (ArticleKind::Romanized, "zǎo shang hǎo".to_string()),
+                (ArticleKind::Alternatives, "早安\n上午好".to_string()),
+            ]
+        );
+    }
+}
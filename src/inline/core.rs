@@ -0,0 +1,707 @@
+use crate::error::ParseError;
+use crate::types::{LanguageCode, ParseOptions, ParsedInlineQuery, UnsupportedSourcePolicy};
+use regex::Regex;
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{LazyLock, Mutex};
+use unicode_normalization::{IsNormalized, UnicodeNormalization, is_nfc_quick};
+use whatlang::detect;
+
+pub const SEGMENT_DELIMITER: &str = "|";
+/// Opens and closes a block of code, as in Markdown.
+pub const CODE_FENCE: &str = "```";
+
+type Direction = (LanguageCode, LanguageCode);
+
+/// `en>zh:` style direction prefix with an optional `#N` alternatives count.
+static DIRECTION_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?i)(en|zh)\s*(?:>|->)\s*(en|zh)(?:\s*#(\d+))?(?:\s*\[([^\]]*)\])?\s*:?").unwrap()
+});
+
+/// `[context]:` prefix for queries without an explicit direction.
+static CONTEXT_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\[([^\]]*)\]\s*:").unwrap());
+
+/// A Latin-script word, for weighing scripts against each other.
+static LATIN_WORD: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[A-Za-z][A-Za-z0-9'’+#-]*").unwrap());
+
+/// Longest context passed on to the provider, in characters.
+const MAX_CONTEXT_LENGTH: usize = 100;
+
+/// `romanize:` prefix asking for the romanization instead of a translation.
+static ROMANIZE_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?i)romani[sz]e\s*:").unwrap());
+
+/// `explain:` prefix asking for an explanation along with the translation.
+static EXPLAIN_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?i)explain\s*:").unwrap());
+
+/// A detected direction and how sure detection was of it, from 0 to 1.
+type Detection = (Direction, f64);
+
+/// Detection outcomes keyed by a hash of the text and the options used,
+/// with keys in insertion order so the oldest is evicted first.
+#[derive(Default)]
+struct DetectionCache {
+    entries: HashMap<u64, Result<Detection, ParseError>>,
+    order: VecDeque<u64>,
+}
+
+impl DetectionCache {
+    /// Stores `detection` under `key`, evicting the oldest entries past
+    /// `capacity`.
+    fn insert(&mut self, key: u64, detection: Result<Detection, ParseError>, capacity: usize) {
+        // Another parse may have detected the same text meanwhile.
+        if self.entries.insert(key, detection).is_none() {
+            self.order.push_back(key);
+        }
+        while self.order.len() > capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+}
+
+static DETECTION_CACHE: LazyLock<Mutex<DetectionCache>> =
+    LazyLock::new(|| Mutex::new(DetectionCache::default()));
+
+/// Any Latin letter, for the last-resort guess in [`auto_detect_direction`].
+static LATIN_LETTER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[a-zA-Z]").unwrap());
+/// Runs of spaces and tabs, collapsed by [`normalize_english`].
+static HORIZONTAL_SPACE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[ \t]+").unwrap());
+/// A contraction split by stray spaces, as in "don ' t".
+static SPLIT_CONTRACTION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b([a-z]+) *(['’]) *(t|s|re|ve|ll|d|m)\b").unwrap());
+/// A whole English word, checked against [`COMMON_TYPOS`].
+static ENGLISH_WORD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b[A-Za-z]+\b").unwrap());
+
+/// Parses raw user input into a translation request.
+///
+/// An optional `en>zh:` style prefix sets the direction explicitly; otherwise
+/// it is detected from the text. `en>zh#5:` additionally asks for five
+/// alternatives. A bracketed context, as in `en>zh [finance]: bank` or
+/// `[finance]: bank`, helps the provider disambiguate without being
+/// translated. `romanize: 你好` asks for the romanization of Chinese text
+/// instead of a translation, and with `explain_mode` set, `explain: 画蛇添足`
+/// for a translation with an explanation of its idiomatic meaning. With
+/// `lock_direction` set, prefixes are not recognised and the defaults always
+/// apply.
+pub fn parse_inline_query(
+    raw_query: &str,
+    options: &ParseOptions,
+) -> Result<ParsedInlineQuery, ParseError> {
+    let sanitized = to_half_width(&to_nfc(sanitize(raw_query)));
+    let trimmed = sanitized.trim();
+    if trimmed.is_empty() {
+        return Err(ParseError::Empty);
+    }
+
+    if let Some(prefix) = ROMANIZE_PATTERN.find(trimmed) {
+        let rest = &trimmed[prefix.end()..];
+        // The text is romanized in its own language; whether that language
+        // has a scheme is up to the translator.
+        let lang = match dominant_script(rest) {
+            Script::Latin => LanguageCode::En,
+            Script::Cjk | Script::Balanced => LanguageCode::Zh,
+        };
+        let text = normalize_segments(
+            &rest
+                .chars()
+                .take(options.max_text_length(lang))
+                .collect::<String>(),
+        );
+        if text.is_empty() {
+            return Err(ParseError::Empty);
+        }
+        return Ok(ParsedInlineQuery {
+            text,
+            source_lang: lang,
+            target_lang: lang,
+            alternative_count: None,
+            dictionary: false,
+            romanize_only: true,
+            explain: false,
+            context: None,
+            detection_confidence: None,
+        });
+    }
+
+    // The rest may carry its own direction and context: `explain: zh>en: 画蛇添足`.
+    if options.explain_mode
+        && let Some(prefix) = EXPLAIN_PATTERN.find(trimmed)
+    {
+        let parsed = parse_inline_query(&trimmed[prefix.end()..], options)?;
+        return Ok(ParsedInlineQuery {
+            explain: !parsed.romanize_only,
+            dictionary: false,
+            ..parsed
+        });
+    }
+
+    let direction = (!options.lock_direction)
+        .then(|| DIRECTION_PATTERN.captures(trimmed))
+        .flatten();
+    let (context, unprefixed) = match (&direction, CONTEXT_PATTERN.captures(trimmed)) {
+        (Some(captures), _) => (captures.get(4).map(|m| m.as_str()), trimmed),
+        (None, Some(captures)) => (
+            captures.get(1).map(|m| m.as_str()),
+            trimmed[captures.get(0).unwrap().end()..].trim(),
+        ),
+        (None, None) => (None, trimmed),
+    };
+    let context = context
+        .map(|context| {
+            context
+                .trim()
+                .chars()
+                .take(MAX_CONTEXT_LENGTH)
+                .collect::<String>()
+        })
+        .filter(|context| !context.is_empty());
+
+    let mut detection_confidence = None;
+    let (source_lang, target_lang, alternative_count, text_portion) = if options.lock_direction {
+        (
+            options.default_source,
+            options.default_target,
+            None,
+            unprefixed,
+        )
+    } else if let Some(captures) = direction {
+        let src = captures.get(1).unwrap().as_str().parse().unwrap();
+        let tgt = captures.get(2).unwrap().as_str().parse().unwrap();
+        let count = captures.get(3).map(|m| {
+            m.as_str()
+                .parse::<usize>()
+                .unwrap_or(usize::MAX)
+                .min(options.max_alternatives)
+        });
+        let text = trimmed[captures.get(0).unwrap().end()..].trim();
+        (src, tgt, count, text)
+    } else {
+        // No explicit direction, try to detect
+        let ((src, tgt), confidence) = detect_direction_cached(unprefixed, options)?;
+        detection_confidence = Some(confidence);
+        (src, tgt, None, unprefixed)
+    };
+
+    let text_portion = if options.normalize_source && source_lang == LanguageCode::En {
+        normalize_english(text_portion)
+    } else {
+        text_portion.to_string()
+    };
+
+    let normalized_text = normalize_segments(
+        &text_portion
+            .chars()
+            .take(options.max_text_length(source_lang))
+            .collect::<String>(),
+    );
+
+    if normalized_text.is_empty() {
+        Err(ParseError::Empty)
+    } else {
+        Ok(ParsedInlineQuery {
+            dictionary: options.dictionary_mode && is_single_word(&normalized_text),
+            romanize_only: false,
+            explain: false,
+            context,
+            detection_confidence,
+            text: normalized_text,
+            source_lang,
+            target_lang,
+            alternative_count,
+        })
+    }
+}
+
+/// Drops invisible characters that confuse detection and can be used for
+/// spoofing: zero-width spaces, BOMs, soft hyphens, bidi embeddings/overrides/
+/// isolates and stray control codes. Exotic spaces become plain spaces.
+/// ZWJ and ZWNJ are kept since emoji sequences and some scripts rely on them.
+pub fn sanitize(text: &str) -> String {
+    text.chars()
+        .filter_map(|c| match c {
+            '\n' | '\t' => Some(c),
+            '\u{200B}' | '\u{2060}' | '\u{FEFF}' | '\u{00AD}' => None,
+            '\u{200E}' | '\u{200F}' | '\u{061C}' => None,
+            '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}' => None,
+            '\u{00A0}' | '\u{2000}'..='\u{200A}' | '\u{202F}' | '\u{205F}' => Some(' '),
+            c if c.is_control() => None,
+            c => Some(c),
+        })
+        .collect()
+}
+
+/// Composes `text` to NFC so the same visible string always yields the same
+/// prompt and cache key. ASCII and already-composed text is returned as is.
+pub fn to_nfc(text: String) -> String {
+    if text.is_ascii() || is_nfc_quick(text.chars()) == IsNormalized::Yes {
+        text
+    } else {
+        text.nfc().collect()
+    }
+}
+
+/// Turns full-width letters and digits ("ＡＢＣ１２３"), as typed by CJK input
+/// methods, into plain ASCII so they are detected and translated as Latin
+/// text. Full-width punctuation is left alone: "，" and "？" are ordinary
+/// Chinese punctuation.
+pub fn to_half_width(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '０'..='９' | 'Ａ'..='Ｚ' | 'ａ'..='ｚ' => {
+                char::from_u32(c as u32 - 0xFEE0).unwrap_or(c)
+            }
+            c => c,
+        })
+        .collect()
+}
+
+/// [`auto_detect_direction`] and [`direction_confidence`] behind a bounded
+/// cache, so repeated parses of the same text skip the regex and `whatlang`
+/// work.
+fn detect_direction_cached(text: &str, options: &ParseOptions) -> Result<Detection, ParseError> {
+    let detect = || {
+        auto_detect_direction(
+            text,
+            options.default_source,
+            options.default_target,
+            options.unsupported_source,
+        )
+        .map(|direction| (direction, direction_confidence(text)))
+    };
+    if options.detection_cache_size == 0 {
+        return detect();
+    }
+
+    let mut hasher = DefaultHasher::new();
+    (
+        text,
+        options.default_source,
+        options.default_target,
+        options.unsupported_source,
+    )
+        .hash(&mut hasher);
+    let key = hasher.finish();
+
+    if let Some(detection) = DETECTION_CACHE.lock().unwrap().entries.get(&key) {
+        return detection.clone();
+    }
+
+    let detection = detect();
+    DETECTION_CACHE
+        .lock()
+        .unwrap()
+        .insert(key, detection.clone(), options.detection_cache_size);
+    detection
+}
+
+/// Script that carries most of a text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Script {
+    /// Chinese characters (and kana).
+    Cjk,
+    Latin,
+    /// Evenly matched, or neither script present.
+    Balanced,
+}
+
+/// Weighs the scripts in `text` against each other. A CJK character carries
+/// about half as much as a Latin word, so "安装 Docker 的步骤" is CJK and
+/// "Hello there 你" is Latin.
+pub fn dominant_script(text: &str) -> Script {
+    let cjk = text.chars().filter(|c| is_cjk(*c)).count();
+    let latin = LATIN_WORD.find_iter(text).count();
+    match cjk.div_ceil(2).cmp(&latin) {
+        Ordering::Greater => Script::Cjk,
+        Ordering::Less => Script::Latin,
+        Ordering::Equal => Script::Balanced,
+    }
+}
+
+/// Kana or Han.
+fn is_cjk(c: char) -> bool {
+    matches!(c, '\u{3040}'..='\u{30FF}') || is_han(c)
+}
+
+/// Han ideographs: the unified block, extension A and the compatibility
+/// ideographs.
+pub fn is_han(c: char) -> bool {
+    matches!(c, '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' | '\u{F900}'..='\u{FAFF}')
+}
+
+/// Confidence at which an unreliable whatlang guess is still trusted.
+const MIN_DETECTION_CONFIDENCE: f64 = 0.5;
+/// Inputs shorter than this fall back to the defaults when detection is unsure.
+const SHORT_TEXT_CHARS: usize = 12;
+
+/// Picks a direction for text without an explicit prefix, falling back to the
+/// defaults when neither script nor detector gives a clear answer. Text
+/// reliably detected as another language is handled per `unsupported`.
+pub fn auto_detect_direction(
+    text: &str,
+    default_source: LanguageCode,
+    default_target: LanguageCode,
+    unsupported: UnsupportedSourcePolicy,
+) -> Result<Direction, ParseError> {
+    // Mixed input goes by whichever script dominates, so incidental Chinese
+    // in English text (or the reverse) does not flip the direction. Evenly
+    // matched input is left to whatlang.
+    let has_cjk = text.chars().any(is_cjk);
+    match dominant_script(text) {
+        Script::Cjk => return Ok((LanguageCode::Zh, LanguageCode::En)),
+        Script::Latin if has_cjk => return Ok((LanguageCode::En, LanguageCode::Zh)),
+        _ => {}
+    }
+
+    // Otherwise, try to detect language using whatlang
+    let info = detect(text);
+    if let Some(info) = &info
+        && (info.is_reliable() || info.confidence() >= MIN_DETECTION_CONFIDENCE)
+    {
+        match info.lang() {
+            whatlang::Lang::Eng => return Ok((LanguageCode::En, LanguageCode::Zh)),
+            whatlang::Lang::Cmn => return Ok((LanguageCode::Zh, LanguageCode::En)),
+            // Only a reliable verdict is worth refusing the user over.
+            other if info.is_reliable() && unsupported == UnsupportedSourcePolicy::Reject => {
+                return Err(ParseError::UnsupportedSource(other.eng_name().to_string()));
+            }
+            _ => {}
+        }
+    }
+
+    // A short string whatlang is unsure about ("ok", "123", "Ω") says little
+    // about its language, so the configured direction beats a guess.
+    let unsure = info.is_none_or(|info| !info.is_reliable());
+    if unsure && text.trim().chars().count() < SHORT_TEXT_CHARS {
+        return Ok((default_source, default_target));
+    }
+
+    // Fallback: if it looks like Latin script but wasn't detected as English, assume English -> Chinese
+    // (e.g. short words, slang, or just defaulting for non-Chinese input)
+    if LATIN_LETTER.is_match(text) {
+        return Ok((LanguageCode::En, LanguageCode::Zh));
+    }
+
+    Ok((default_source, default_target))
+}
+
+/// How sure [`auto_detect_direction`] can be about `text`: certain when one
+/// script clearly dominates a mix of both, otherwise whatlang's confidence.
+pub fn direction_confidence(text: &str) -> f64 {
+    let has_cjk = text.chars().any(is_cjk);
+    match dominant_script(text) {
+        Script::Cjk => 1.0,
+        Script::Latin if has_cjk => 1.0,
+        _ => detect(text).map_or(0.0, |info| info.confidence()),
+    }
+}
+
+/// Longest unspaced Han input still treated as a single word.
+const MAX_CJK_WORD_CHARS: usize = 4;
+
+/// Whether `text` is one word: letters with optional inner hyphens or
+/// apostrophes. Han text has no spaces, so only very short runs count there.
+pub fn is_single_word(text: &str) -> bool {
+    !text.is_empty()
+        && text.chars().next().is_some_and(char::is_alphabetic)
+        && text
+            .chars()
+            .all(|c| c.is_alphabetic() || c == '-' || c == '\'' || c == '’')
+        && (!text.chars().any(is_han) || text.chars().count() <= MAX_CJK_WORD_CHARS)
+}
+
+/// Misspellings with exactly one plausible correction.
+const COMMON_TYPOS: &[(&str, &str)] = &[
+    ("teh", "the"),
+    ("adn", "and"),
+    ("recieve", "receive"),
+    ("recieved", "received"),
+    ("beleive", "believe"),
+    ("becuase", "because"),
+    ("definately", "definitely"),
+    ("seperate", "separate"),
+    ("occured", "occurred"),
+    ("untill", "until"),
+    ("wich", "which"),
+    ("thier", "their"),
+    ("tommorow", "tomorrow"),
+    ("goverment", "government"),
+];
+
+/// Conservative clean-up of English input: collapses runs of spaces, rejoins
+/// contractions split by stray spaces ("don ' t") and fixes a short list of
+/// unambiguous typos. Line breaks are kept as they are.
+fn normalize_english(text: &str) -> String {
+    let collapsed = HORIZONTAL_SPACE.replace_all(text, " ");
+    let rejoined = SPLIT_CONTRACTION.replace_all(&collapsed, "$1$2$3");
+    ENGLISH_WORD
+        .replace_all(&rejoined, |caps: &regex::Captures| {
+            let original = &caps[0];
+            let lower = original.to_lowercase();
+            match COMMON_TYPOS.iter().find(|(typo, _)| *typo == lower) {
+                Some((_, fixed)) if original.starts_with(char::is_uppercase) => {
+                    let mut chars = fixed.chars();
+                    chars
+                        .next()
+                        .map(|first| first.to_uppercase().chain(chars).collect())
+                        .unwrap_or_default()
+                }
+                Some((_, fixed)) => fixed.to_string(),
+                None => original.to_string(),
+            }
+        })
+        .into_owned()
+}
+
+/// Trims each `|`-separated segment and drops empty ones.
+pub fn normalize_segments(raw: &str) -> String {
+    split_segments(raw)
+        .iter()
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(SEGMENT_DELIMITER)
+}
+
+/// Puts each `|`-separated segment on its own line.
+pub fn format_segments_for_display(value: &str) -> String {
+    split_segments(value)
+        .iter()
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Splits `text` at the segment delimiter, except inside ``` fenced code,
+/// where `|` is usually an operator.
+fn split_segments(text: &str) -> Vec<String> {
+    let mut segments = vec![String::new()];
+    for (index, part) in text.split(CODE_FENCE).enumerate() {
+        let current = segments.last_mut().unwrap();
+        if index > 0 {
+            current.push_str(CODE_FENCE);
+        }
+        if index % 2 == 1 {
+            current.push_str(part);
+            continue;
+        }
+        let mut pieces = part.split(SEGMENT_DELIMITER);
+        current.push_str(pieces.next().unwrap_or_default());
+        segments.extend(pieces.map(str::to_string));
+    }
+    segments
+}
+
+/// Collapses `s` onto one line and shortens it to `max` characters.
+pub fn truncate(s: &str, max: usize) -> String {
+    let single_line = s.replace(char::is_whitespace, " ");
+    let trimmed = single_line.trim();
+    if trimmed.chars().count() > max {
+        trimmed.chars().take(max - 1).collect::<String>() + "…"
+    } else {
+        trimmed.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::DEFAULT_MAX_TEXT_LENGTH;
+
+    fn options() -> ParseOptions {
+        ParseOptions {
+            default_source: LanguageCode::En,
+            default_target: LanguageCode::Zh,
+            normalize_source: false,
+            max_alternatives: 5,
+            dictionary_mode: false,
+            detection_cache_size: 0,
+            unsupported_source: UnsupportedSourcePolicy::Guess,
+            lock_direction: false,
+            explain_mode: false,
+            max_text_lengths: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn normalize_source_collapses_spaces_and_rejoins_contractions() {
+        let options = ParseOptions {
+            normalize_source: true,
+            ..options()
+        };
+        let parsed = parse_inline_query("en>zh: I   don ' t\tknow teh answer", &options).unwrap();
+        assert_eq!(parsed.text, "I don't know the answer");
+    }
+
+    #[test]
+    fn detection_cache_evicts_the_oldest_entry() {
+        let mut cache = DetectionCache::default();
+        let detection = Ok(((LanguageCode::En, LanguageCode::Zh), 1.0));
+        for key in 0..3 {
+            cache.insert(key, detection.clone(), 2);
+        }
+        assert!(!cache.entries.contains_key(&0));
+        assert!(cache.entries.contains_key(&1) && cache.entries.contains_key(&2));
+        assert_eq!(cache.order, [1, 2]);
+    }
+
+    #[test]
+    fn detection_cache_does_not_queue_a_key_twice() {
+        let mut cache = DetectionCache::default();
+        let detection = Ok(((LanguageCode::En, LanguageCode::Zh), 1.0));
+        cache.insert(7, detection.clone(), 2);
+        cache.insert(7, detection, 2);
+        assert_eq!(cache.order, [7]);
+    }
+
+    #[test]
+    fn invisible_and_bidi_characters_are_removed() {
+        let parsed =
+            parse_inline_query("en>zh: pay\u{200B}pal \u{202E}gpj.exe", &options()).unwrap();
+        assert_eq!(parsed.text, "paypal gpj.exe");
+    }
+
+    #[test]
+    fn combining_marks_and_line_breaks_are_kept() {
+        assert_eq!(sanitize("cafe\u{301}\nnext"), "cafe\u{301}\nnext");
+    }
+
+    #[test]
+    fn nfc_and_nfd_input_parse_to_the_same_text() {
+        let composed = parse_inline_query("en>zh: café crème", &options()).unwrap();
+        let decomposed = parse_inline_query("en>zh: cafe\u{301} cre\u{300}me", &options()).unwrap();
+        assert_eq!(composed.text, "café crème");
+        assert_eq!(decomposed.text, composed.text);
+    }
+
+    #[test]
+    fn dominant_script_weighs_cjk_against_latin_words() {
+        assert_eq!(dominant_script("Hello there 你"), Script::Latin);
+        assert_eq!(dominant_script("安装 Docker 的步骤"), Script::Cjk);
+        assert_eq!(dominant_script("你好世界"), Script::Cjk);
+        assert_eq!(dominant_script("plain English text"), Script::Latin);
+        // Two CJK characters weigh as much as one Latin word.
+        assert_eq!(dominant_script("你好 hello"), Script::Balanced);
+        assert_eq!(dominant_script("123 !?"), Script::Balanced);
+    }
+
+    #[test]
+    fn incidental_cjk_does_not_flip_the_direction() {
+        let detect = |text| {
+            auto_detect_direction(
+                text,
+                LanguageCode::En,
+                LanguageCode::Zh,
+                UnsupportedSourcePolicy::Guess,
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            detect("Hello 你, how are you doing today?"),
+            (LanguageCode::En, LanguageCode::Zh)
+        );
+        assert_eq!(
+            detect("我们明天去 Starbucks 喝咖啡吧"),
+            (LanguageCode::Zh, LanguageCode::En)
+        );
+    }
+
+    #[test]
+    fn balanced_input_is_left_to_whatlang() {
+        let text = "你好 hello";
+        assert_eq!(dominant_script(text), Script::Balanced);
+        // Whatlang has the final say, with the confidence to match.
+        assert_eq!(
+            direction_confidence(text),
+            detect(text).map_or(0.0, |info| info.confidence())
+        );
+    }
+
+    #[test]
+    fn full_width_letters_and_digits_become_ascii() {
+        assert_eq!(to_half_width("ＡＢＣ１２３ｘｙｚ"), "ABC123xyz");
+        assert_eq!(to_half_width("版本Ｖ２，好吗？"), "版本V2，好吗？");
+
+        let parsed = parse_inline_query("Ｈｅｌｌｏ ｗｏｒｌｄ", &options()).unwrap();
+        assert_eq!(parsed.text, "Hello world");
+        assert_eq!(
+            (parsed.source_lang, parsed.target_lang),
+            (LanguageCode::En, LanguageCode::Zh)
+        );
+    }
+
+    #[test]
+    fn ambiguous_short_input_keeps_the_default_direction() {
+        let options = ParseOptions {
+            default_source: LanguageCode::Zh,
+            default_target: LanguageCode::En,
+            ..options()
+        };
+        for text in ["ok", "123", "Ω"] {
+            let parsed = parse_inline_query(text, &options).unwrap();
+            assert_eq!(
+                (parsed.source_lang, parsed.target_lang),
+                (LanguageCode::Zh, LanguageCode::En),
+                "{:?}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn french_input_is_rejected_or_guessed_per_policy() {
+        let text = "Je voudrais réserver une table pour deux personnes ce soir, s'il vous plaît.";
+        let reject = ParseOptions {
+            unsupported_source: UnsupportedSourcePolicy::Reject,
+            ..options()
+        };
+        assert_eq!(
+            parse_inline_query(text, &reject).unwrap_err(),
+            ParseError::UnsupportedSource("French".to_string())
+        );
+
+        let parsed = parse_inline_query(text, &options()).unwrap();
+        assert_eq!(
+            (parsed.source_lang, parsed.target_lang),
+            (LanguageCode::En, LanguageCode::Zh)
+        );
+    }
+
+    #[test]
+    fn locked_direction_ignores_prefixes() {
+        let locked = ParseOptions {
+            lock_direction: true,
+            ..options()
+        };
+        let parsed = parse_inline_query("zh>en: good morning", &locked).unwrap();
+        assert_eq!(
+            (parsed.source_lang, parsed.target_lang),
+            (LanguageCode::En, LanguageCode::Zh)
+        );
+        assert_eq!(parsed.text, "zh>en: good morning");
+        assert_eq!(parsed.detection_confidence, None);
+    }
+
+    #[test]
+    fn input_is_cut_at_the_source_language_limit() {
+        let limited = ParseOptions {
+            max_text_lengths: HashMap::from([(LanguageCode::En, 10), (LanguageCode::Zh, 4)]),
+            ..options()
+        };
+        let english = parse_inline_query("en>zh: hello world again", &limited).unwrap();
+        assert_eq!(english.text, "hello worl");
+        let chinese = parse_inline_query("zh>en: 你好世界再见", &limited).unwrap();
+        assert_eq!(chinese.text, "你好世界");
+
+        // Languages without a limit of their own use the default.
+        let long = "a".repeat(DEFAULT_MAX_TEXT_LENGTH + 10);
+        let unlimited = parse_inline_query(&format!("en>zh: {}", long), &options()).unwrap();
+        assert_eq!(unlimited.text.chars().count(), DEFAULT_MAX_TEXT_LENGTH);
+    }
+}
@@ -0,0 +1,41 @@
+//! Inline translation bot core.
+//!
+//! The binary in `main.rs` wires these modules into a teloxide dispatcher, but
+//! everything here can be used on its own: build a [`config::Config`], hand it
+//! to [`translator::Translator::new`], and feed user input through
+//! [`inline::parse_inline_query`].
+//!
+//! The `bot` feature (on by default) pulls in the HTTP client and Telegram
+//! types. Without it only the pure parsing and text helpers are built, which
+//! is enough for `wasm32-unknown-unknown`.
+
+pub mod albums;
+pub mod audit;
+pub mod cache;
+pub mod chat_slots;
+pub mod chosen_results;
+pub mod circuit;
+pub mod commands;
+pub mod config;
+pub mod deep_links;
+pub mod error;
+pub mod history;
+pub mod i18n;
+pub mod inline;
+pub mod notes;
+#[cfg(feature = "bot")]
+pub mod ocr;
+pub mod pending;
+pub mod preprocess;
+pub mod profanity;
+pub mod protect;
+pub mod punctuation;
+pub mod romanizations;
+pub mod telegram;
+#[cfg(feature = "bot")]
+pub mod translator;
+#[cfg(feature = "bot")]
+pub mod tts;
+pub mod types;
+pub mod update_slots;
+pub mod verbosity;
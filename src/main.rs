@@ -1,19 +1,180 @@
-mod config;
-mod inline;
-mod translator;
-mod types;
-
-use crate::config::Config;
-use crate::translator::Translator;
+use rust_bot::albums::AlbumStore;
+use rust_bot::audit::{AuditEntry, AuditSink, JsonlAuditSink, NoopAuditSink};
+use rust_bot::chat_slots::{ChatSlot, ChatSlots};
+use rust_bot::chosen_results::ChosenResultStore;
+use rust_bot::commands::Command;
+use rust_bot::config::{CacheBackend, ChannelPostMode, Config, LogFormat};
+use rust_bot::deep_links::DeepLinkStore;
+use rust_bot::error::{ErrorDetail, ParseError, TranslationError};
+use rust_bot::history::HistoryStore;
+use rust_bot::i18n::{self, Msg, UiLanguage};
+use rust_bot::inline::{self, core::truncate};
+use rust_bot::ocr::OcrClient;
+use rust_bot::pending::PendingInputs;
+use rust_bot::romanizations::RomanizationStore;
+use rust_bot::telegram::{CAPTION_LIMIT, MESSAGE_LIMIT, escape_html, split_for_telegram};
+use rust_bot::translator::Translator;
+use rust_bot::tts::TtsClient;
+use rust_bot::types::{LanguageCode, ParsedInlineQuery, TranslationRequest, TranslationResult};
+use rust_bot::update_slots::{UpdateSlot, UpdateSlots};
+use rust_bot::verbosity::{Verbosity, VerbosityStore};
 use std::sync::Arc;
+use std::time::Duration;
+use teloxide::net::Download;
 use teloxide::prelude::*;
-use tracing::{error, info};
+use teloxide::types::{
+    BotCommand, ForceReply, InlineKeyboardButton, InlineKeyboardMarkup, InlineQueryResultsButton,
+    InlineQueryResultsButtonKind, InputFile, ParseMode, ReplyParameters, UpdateKind,
+};
+use tokio::task::JoinSet;
+use tracing::{debug, error, info, warn};
+use tracing_subscriber::EnvFilter;
+use uuid::Uuid;
+
+/// How long a query handed to the private chat by the inline button waits
+/// for the user to open it.
+const DEEP_LINK_TTL: Duration = Duration::from_secs(3600);
+/// Most queries waiting for the user to open the private chat.
+const DEEP_LINK_CAPACITY: usize = 10000;
+/// How long an offered inline translation can still be recorded in history
+/// when the user picks it.
+const CHOSEN_RESULT_TTL: Duration = Duration::from_secs(600);
+/// Most offered inline translations kept for chosen-result feedback.
+const CHOSEN_RESULT_CAPACITY: usize = 10000;
+/// Callback data of the button that translates a DM the other way.
+const SWAP_DIRECTION_CALLBACK: &str = "swap";
+/// Entries shown per `/history` page.
+const HISTORY_PAGE_SIZE: usize = 10;
+/// Alternatives requested for DM replies with `dm_show_alternatives` when
+/// the user did not ask for a number with `#N`.
+const DM_ALTERNATIVES: usize = 3;
+
+/// `translate [--from LANG] [--to LANG] [--json] [TEXT...]`: translates
+/// TEXT (or stdin when none is given) without starting the bot, printing the
+/// translation or, with `--json`, the full result.
+async fn run_cli(args: &[String]) -> anyhow::Result<()> {
+    const USAGE: &str = "usage: translate [--from en|zh] [--to en|zh] [--json] [TEXT...]";
+    let parse_lang = |value: Option<&String>| -> anyhow::Result<LanguageCode> {
+        value
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| anyhow::anyhow!(USAGE))
+    };
+
+    let (mut from, mut to, mut json) = (None, None, false);
+    let mut words = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--from" => from = Some(parse_lang(args.next())?),
+            "--to" => to = Some(parse_lang(args.next())?),
+            "--json" => json = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ => words.push(arg.as_str()),
+        }
+    }
+    let text = if words.is_empty() {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        words.join(" ")
+    };
+
+    // The bot token is never used here.
+    let config = Config::from_env_without_bot()?;
+
+    let parsed = inline::parse_inline_query(&text, &config.parse_options())?;
+    let mut request = TranslationRequest::from(&parsed);
+    if let Some(from) = from {
+        request.source_lang = from;
+    }
+    if let Some(to) = to {
+        request.target_lang = to;
+    }
+
+    let translator = Translator::new(config)?;
+    let translation = translator.translate(request).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&translation)?);
+    } else {
+        println!("{}", translation.primary_text);
+    }
+    Ok(())
+}
+
+/// Publishes the command menu, so Telegram can autocomplete our commands.
+/// Failure is logged and otherwise ignored: commands still work when typed.
+async fn register_commands(bot: &Bot, lang: UiLanguage) {
+    let commands = Command::ALL
+        .into_iter()
+        .map(|command| BotCommand::new(command.name(), i18n::text(lang, command.description())))
+        .collect::<Vec<_>>();
+    if let Err(e) = bot.set_my_commands(commands).await {
+        warn!("Failed to register bot commands: {}", e);
+    }
+}
+
+/// Levels from `RUST_LOG`, or `info` and up when it is unset or invalid.
+fn log_filter() -> EnvFilter {
+    EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"))
+}
+
+/// Installs the global subscriber. In JSON, fields of the enclosing spans
+/// (such as a translation's `request_id`) are repeated on every line.
+fn init_logging(format: LogFormat) {
+    let builder = tracing_subscriber::fmt().with_env_filter(log_filter());
+    match format {
+        LogFormat::Pretty => builder.init(),
+        LogFormat::Json => builder
+            .json()
+            .flatten_event(true)
+            .with_current_span(false)
+            .with_span_list(true)
+            .init(),
+    }
+}
+
+/// State shared by the handlers: short-lived in-memory stores and the
+/// audit log.
+struct Stores {
+    pending: PendingInputs,
+    romanizations: RomanizationStore,
+    chosen: ChosenResultStore,
+    deep_links: DeepLinkStore,
+    albums: AlbumStore,
+    chat_slots: ChatSlots,
+    history: HistoryStore,
+    verbosity: VerbosityStore,
+    updates: UpdateSlots,
+    audit: Box<dyn AuditSink>,
+}
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "translate") {
+        // Logs go to stderr so stdout holds nothing but the translation.
+        tracing_subscriber::fmt()
+            .with_env_filter(log_filter())
+            .with_writer(std::io::stderr)
+            .init();
+        if let Err(e) = run_cli(&args[1..]).await {
+            eprintln!("{:#}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    let config = match Config::from_env() {
+    // Logging is set up before the config is checked, so that a config
+    // error can be logged; a broken config gets the default format.
+    let config = Config::from_env();
+    init_logging(
+        config
+            .as_ref()
+            .map_or(LogFormat::Pretty, |config| config.log_format),
+    );
+    let config = match config {
         Ok(c) => c,
         Err(e) => {
             error!("Failed to load config: {}", e);
@@ -29,60 +190,235 @@ async fn main() {
         }
     };
 
+    let tts = match TtsClient::from_config(&config) {
+        Ok(t) => t.map(Arc::new),
+        Err(e) => {
+            error!("Failed to initialize TTS client: {}", e);
+            return;
+        }
+    };
+
+    let ocr = match OcrClient::from_config(&config) {
+        Ok(o) => o.map(Arc::new),
+        Err(e) => {
+            error!("Failed to initialize OCR client: {}", e);
+            return;
+        }
+    };
+
+    if config.cache_backend == CacheBackend::Memory {
+        let cache = translator.cache();
+        let period = Duration::from_secs(config.cache_sweep_interval_secs);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                let swept = cache.sweep();
+                if swept > 0 {
+                    debug!("Swept {} expired cache entries", swept);
+                }
+            }
+        });
+    }
+
+    if config.provider_health_interval_secs > 0 {
+        let period = Duration::from_secs(config.provider_health_interval_secs);
+        tokio::spawn(Arc::clone(&translator).monitor_health(period));
+    }
+
+    if let Some(path) = &config.warmup_phrases_path {
+        match std::fs::read_to_string(path) {
+            Ok(_) if config.cache_backend == CacheBackend::None => {
+                warn!("Skipping cache warmup: the cache is disabled");
+            }
+            Ok(contents) => {
+                let options = config.parse_options();
+                let requests = contents
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .filter_map(|line| inline::parse_inline_query(line, &options).ok())
+                    .map(|parsed| TranslationRequest::from(&parsed))
+                    .collect::<Vec<_>>();
+                info!("Warming the cache with {} phrases", requests.len());
+                // Runs alongside the dispatcher; early queries just miss.
+                tokio::spawn(Arc::clone(&translator).warm_up(requests, config.warmup_concurrency));
+            }
+            Err(e) => warn!("Could not read warmup phrases from {}: {}", path, e),
+        }
+    }
+
     let bot = Bot::new(config.bot_token.clone());
 
+    // A bad token is otherwise only noticed through errors from the polling loop.
+    match bot.get_me().await {
+        Ok(me) => info!("Logged in as @{}", me.username()),
+        Err(e) => {
+            error!("Failed to authenticate with BOT_TOKEN: {}", e);
+            return;
+        }
+    }
+
+    register_commands(&bot, config.ui_language).await;
+
     info!("Starting inline translator bot...");
 
     let handler = dptree::entry()
         .branch(Update::filter_inline_query().endpoint(handle_inline_query))
-        .branch(Update::filter_message().endpoint(handle_message));
+        .branch(Update::filter_message().endpoint(handle_message))
+        .branch(Update::filter_channel_post().endpoint(handle_channel_post))
+        .branch(Update::filter_callback_query().endpoint(handle_callback_query))
+        .branch(Update::filter_chosen_inline_result().endpoint(handle_chosen_inline_result));
+
+    let audit: Box<dyn AuditSink> = match &config.audit_log_path {
+        Some(path) => match JsonlAuditSink::open(path) {
+            Ok(sink) => Box::new(sink),
+            Err(e) => {
+                error!("Failed to open audit log {}: {}", path, e);
+                return;
+            }
+        },
+        None => Box::new(NoopAuditSink),
+    };
+
+    let stores = Arc::new(Stores {
+        pending: PendingInputs::new(Duration::from_secs(config.pending_input_timeout_secs)),
+        romanizations: RomanizationStore::new(
+            Duration::from_secs(config.romanization_ttl_secs),
+            config.romanization_store_capacity,
+        ),
+        chosen: ChosenResultStore::new(CHOSEN_RESULT_TTL, CHOSEN_RESULT_CAPACITY),
+        deep_links: DeepLinkStore::new(DEEP_LINK_TTL, DEEP_LINK_CAPACITY),
+        albums: AlbumStore::new(Duration::from_secs(config.album_ttl_secs)),
+        chat_slots: ChatSlots::new(config.max_inflight_per_chat),
+        history: HistoryStore::new(if config.store_history {
+            config.history_limit
+        } else {
+            0
+        }),
+        verbosity: VerbosityStore::new(),
+        updates: UpdateSlots::new(config.max_concurrent_updates),
+        audit,
+    });
 
     // Wrap dependencies in Arc for the handler
     let config_arc = Arc::new(config);
 
     Dispatcher::builder(bot, handler)
-        .dependencies(dptree::deps![translator, config_arc])
+        .dependencies(dptree::deps![translator, config_arc, stores, tts, ocr])
+        .distribution_function(distribution_key)
         .enable_ctrlc_handler()
         .build()
         .dispatch()
         .await;
 }
 
+/// Which updates are handled one after another. Messages are serialized per
+/// user within a chat, so members of a group are translated side by side
+/// under `max_inflight_per_chat`; other updates with a chat, like channel
+/// posts, keep teloxide's per-chat order. Inline queries have no chat and
+/// run concurrently, so a new keystroke never waits behind the last one.
+fn distribution_key(update: &Update) -> Option<(ChatId, Option<UserId>)> {
+    let chat_id = update.chat()?.id;
+    match &update.kind {
+        UpdateKind::Message(msg) => Some((chat_id, msg.from.as_ref().map(|user| user.id))),
+        _ => Some((chat_id, None)),
+    }
+}
+
 async fn handle_inline_query(
     bot: Bot,
     q: InlineQuery,
     translator: Arc<Translator>,
     config: Arc<Config>,
+    stores: Arc<Stores>,
 ) -> ResponseResult<()> {
+    let Some(_update) = stores.updates.try_acquire() else {
+        warn!(
+            in_flight = stores.updates.in_flight(),
+            "Turning away inline query"
+        );
+        let busy_article = inline::build_busy_article(config.ui_language);
+        if let Err(e) = bot
+            .answer_inline_query(q.id, vec![busy_article])
+            .cache_time(0)
+            .is_personal(true)
+            .await
+        {
+            error!("Failed to answer inline query (busy): {}", e);
+        }
+        return Ok(());
+    };
     let raw_query = q.query;
-    let parsed = inline::parse_inline_query(
-        &raw_query,
-        config.default_source_lang,
-        config.default_target_lang,
-    );
+    let parsed = inline::parse_inline_query(&raw_query, &config.parse_options());
+    // Continued through `/start <key>` in the private chat.
+    let pm_button = (config.inline_pm_button
+        && raw_query.trim().chars().count() > config.inline_pm_threshold)
+        .then(|| {
+            let key = Uuid::new_v4().simple().to_string();
+            stores
+                .deep_links
+                .insert(key.clone(), q.from.id.0, raw_query.clone());
+            InlineQueryResultsButton {
+                text: i18n::text(config.ui_language, Msg::OpenInChat).to_string(),
+                kind: InlineQueryResultsButtonKind::StartParameter(key),
+            }
+        });
 
-    if let Some(parsed_query) = parsed {
-        match translator
-            .translate(crate::types::TranslationRequest {
-                text: parsed_query.text.clone(),
-                source_lang: parsed_query.source_lang,
-                target_lang: parsed_query.target_lang,
-            })
-            .await
+    match parsed {
+        Ok(parsed_query) => match translate_with_deadline(
+            &translator,
+            TranslationRequest::from(&parsed_query),
+            config.inline_deadline_ms,
+        )
+        .await
         {
-            Ok(translation) => {
-                let results = inline::build_translation_articles(&parsed_query, &translation);
-                if let Err(e) = bot
+            Some(Ok(translation)) => {
+                audit(&stores, &config, Some(q.from.id.0), &parsed_query).await;
+                let romanization_key = match &translation.romanized_text {
+                    Some(romanized) if config.romanization_button => {
+                        let key = Uuid::new_v4().simple().to_string();
+                        stores.romanizations.insert(key.clone(), romanized.clone());
+                        Some(key)
+                    }
+                    _ => None,
+                };
+                let mut results = inline::build_translation_articles(
+                    &parsed_query,
+                    &translation,
+                    &config,
+                    romanization_key.as_deref(),
+                );
+                if stores.verbosity.get(q.from.id.0) == Verbosity::Terse {
+                    results.truncate(1);
+                }
+                // Truncating keeps the results listed first in `inline_result_order`.
+                if results.len() > config.max_inline_results {
+                    info!(
+                        "Truncating {} inline results to {}",
+                        results.len(),
+                        config.max_inline_results
+                    );
+                    results.truncate(config.max_inline_results);
+                }
+                if stores.history.is_enabled() {
+                    for (id, entry) in inline::chosen_entries(&results, &parsed_query, &translation)
+                    {
+                        stores.chosen.insert(id, entry);
+                    }
+                }
+                let mut answer = bot
                     .answer_inline_query(q.id, results)
                     .cache_time(0)
-                    .is_personal(true)
-                    .await
-                {
+                    .is_personal(true);
+                answer.button = pm_button;
+                if let Err(e) = answer.await {
                     error!("Failed to answer inline query: {}", e);
                 }
             }
-            Err(e) => {
-                let error_article = inline::build_error_article(&e.to_string());
+            Some(Err(e)) => {
+                let message = failure_message(&e, config.user_error_detail);
+                let error_article = inline::build_error_article(&message, config.ui_language);
                 if let Err(e) = bot
                     .answer_inline_query(q.id, vec![error_article])
                     .cache_time(0)
@@ -92,19 +428,199 @@ async fn handle_inline_query(
                     error!("Failed to answer inline query with error: {}", e);
                 }
             }
+            None => {
+                let pending_article = inline::build_pending_article(config.ui_language);
+                let mut answer = bot
+                    .answer_inline_query(q.id, vec![pending_article])
+                    .cache_time(0)
+                    .is_personal(true);
+                answer.button = pm_button;
+                if let Err(e) = answer.await {
+                    error!("Failed to answer inline query (pending): {}", e);
+                }
+            }
+        },
+        Err(ParseError::Empty) => {
+            let history = if config.empty_query_shows_history {
+                stores.history.recent(q.from.id.0)
+            } else {
+                Vec::new()
+            };
+            let results = if history.is_empty() {
+                vec![inline::build_help_article(&config)]
+            } else {
+                inline::build_history_articles(&history, &config)
+            };
+            if let Err(e) = bot
+                .answer_inline_query(q.id, results)
+                .cache_time(0)
+                .is_personal(true)
+                .await
+            {
+                error!("Failed to answer inline query (help): {}", e);
+            }
+        }
+        Err(e) => {
+            let error_article = inline::build_error_article(&e, config.ui_language);
+            if let Err(e) = bot
+                .answer_inline_query(q.id, vec![error_article])
+                .cache_time(0)
+                .is_personal(true)
+                .await
+            {
+                error!("Failed to answer inline query with error: {}", e);
+            }
         }
+    }
+    Ok(())
+}
+
+/// Translates in a task of its own and waits at most `deadline_ms` (0 waits
+/// as long as it takes). `None` when the deadline passed first: Telegram
+/// drops late answers, and the translation carries on into the cache for
+/// the user's next try.
+async fn translate_with_deadline(
+    translator: &Arc<Translator>,
+    request: TranslationRequest,
+    deadline_ms: u64,
+) -> Option<Result<TranslationResult, TranslationError>> {
+    let translator = Arc::clone(translator);
+    let task = tokio::spawn(async move { translator.translate(request).await });
+    let joined = if deadline_ms == 0 {
+        task.await
     } else {
-        let help_article =
-            inline::build_help_article(config.default_source_lang, config.default_target_lang);
-        if let Err(e) = bot
-            .answer_inline_query(q.id, vec![help_article])
-            .cache_time(0)
-            .is_personal(true)
+        tokio::time::timeout(Duration::from_millis(deadline_ms), task)
             .await
-        {
-            error!("Failed to answer inline query (help): {}", e);
-        }
+            .ok()?
+    };
+    Some(joined.unwrap_or_else(|e| Err(TranslationError::Other(e.to_string()))))
+}
+
+/// Logs which kind of inline result users pick and records picked
+/// translations in the user's history. Telegram only sends these when inline
+/// feedback is enabled for the bot in @BotFather.
+async fn handle_chosen_inline_result(
+    result: ChosenInlineResult,
+    stores: Arc<Stores>,
+) -> ResponseResult<()> {
+    match inline::ArticleKind::from_result_id(&result.result_id) {
+        Some(kind) => info!("Chosen inline result: {:?}", kind),
+        None => debug!("Chosen inline result with unknown id {}", result.result_id),
     }
+
+    // Recorded when the articles were built; a miss means the entry expired
+    // or the result was not a translation.
+    if let Some(entry) = stores.chosen.get(&result.result_id) {
+        stores.history.record(
+            result.from.id.0,
+            &entry.text,
+            entry.source_lang,
+            entry.target_lang,
+            &entry.translation,
+        );
+    }
+    Ok(())
+}
+
+/// Answers "Show pinyin" presses with the stored romanization as an alert.
+async fn handle_callback_query(
+    bot: Bot,
+    q: CallbackQuery,
+    translator: Arc<Translator>,
+    config: Arc<Config>,
+    stores: Arc<Stores>,
+) -> ResponseResult<()> {
+    if q.data.as_deref() == Some(SWAP_DIRECTION_CALLBACK) {
+        return translate_other_way(&bot, q, &translator, &config, &stores).await;
+    }
+    let Some(key) = q
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix(inline::ROMANIZATION_CALLBACK_PREFIX))
+    else {
+        bot.answer_callback_query(q.id).await?;
+        return Ok(());
+    };
+
+    // Alerts are capped at 200 characters.
+    let text = match stores.romanizations.get(key) {
+        Some(romanized) => truncate(&romanized, 200),
+        None => i18n::text(config.ui_language, Msg::RomanizationExpired).to_string(),
+    };
+    bot.answer_callback_query(q.id)
+        .text(text)
+        .show_alert(true)
+        .await?;
+    Ok(())
+}
+
+/// Answers the button under an unsure DM translation: translates the
+/// message it replies to with source and target swapped, in its place.
+async fn translate_other_way(
+    bot: &Bot,
+    q: CallbackQuery,
+    translator: &Translator,
+    config: &Config,
+    stores: &Stores,
+) -> ResponseResult<()> {
+    let lang = config.ui_language;
+    let Some((message, original)) = q.regular_message().and_then(|message| {
+        let original = message.reply_to_message()?.text()?;
+        Some((message, original))
+    }) else {
+        bot.answer_callback_query(q.id)
+            .text(i18n::text(lang, Msg::SwapSourceMissing))
+            .show_alert(true)
+            .await?;
+        return Ok(());
+    };
+    let parsed = match inline::parse_inline_query(original, &config.parse_options()) {
+        Ok(parsed) => ParsedInlineQuery {
+            source_lang: parsed.target_lang,
+            target_lang: parsed.source_lang,
+            ..parsed
+        },
+        Err(e) => {
+            bot.answer_callback_query(q.id)
+                .text(truncate(&e.to_string(), 200))
+                .show_alert(true)
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let reply = match translator
+        .translate(TranslationRequest::from(&parsed))
+        .await
+    {
+        Ok(translation) => {
+            audit(stores, config, Some(q.from.id.0), &parsed).await;
+            stores.history.record(
+                q.from.id.0,
+                &parsed.text,
+                parsed.source_lang,
+                parsed.target_lang,
+                &translation.primary_text,
+            );
+            format!(
+                "{}\n\n{}",
+                config.direction_header(parsed.source_lang, parsed.target_lang),
+                translation.primary_text
+            )
+        }
+        Err(e) => i18n::fill(
+            i18n::text(lang, Msg::TranslationFailed),
+            &[&failure_message(&e, config.user_error_detail)],
+        ),
+    };
+    // Editing drops the button, so it cannot be pressed twice.
+    let text = split_for_telegram(&reply, MESSAGE_LIMIT)
+        .into_iter()
+        .next()
+        .unwrap_or_default();
+    bot.edit_message_text(message.chat.id, message.id, text)
+        .await?;
+    bot.answer_callback_query(q.id).await?;
     Ok(())
 }
 
@@ -113,64 +629,909 @@ async fn handle_message(
     msg: Message,
     translator: Arc<Translator>,
     config: Arc<Config>,
+    stores: Arc<Stores>,
+    tts: Option<Arc<TtsClient>>,
+    ocr: Option<Arc<OcrClient>>,
 ) -> ResponseResult<()> {
+    let tts = tts.as_deref();
+    let lang = config.ui_language;
+
+    if let (Some(group), Some(text)) = (msg.media_group_id(), msg.text().or(msg.caption())) {
+        stores.albums.record(&group.0, msg.id.0, text);
+    }
+
+    // Captioned photos are left alone: the caption says what the user wants.
+    if let (Some(_), None, Some(ocr)) = (msg.photo(), msg.caption(), ocr.as_deref()) {
+        let Some(_slots) = take_slots(&bot, &stores, msg.chat.id, lang).await? else {
+            return Ok(());
+        };
+        return translate_photo(&bot, &msg, ocr, &translator, &config, tts, &stores).await;
+    }
+    // Stickers carry no text of their own; their set's title is the
+    // closest thing to it. Sets without one, or that fail to load, are
+    // skipped quietly.
+    if let Some(set_name) = msg.sticker().and_then(|sticker| sticker.set_name.as_ref())
+        && config.translate_sticker_meta
+    {
+        let title = match bot.get_sticker_set(set_name).await {
+            Ok(set) => set.title,
+            Err(e) => {
+                debug!("Could not load sticker set {}: {}", set_name, e);
+                return Ok(());
+            }
+        };
+        if title.trim().is_empty() {
+            return Ok(());
+        }
+        let Some(_slots) = take_slots(&bot, &stores, msg.chat.id, lang).await? else {
+            return Ok(());
+        };
+        return translate_and_reply(&bot, &msg, &title, &translator, &config, tts, &stores).await;
+    }
     if let Some(text) = msg.text() {
         if text.starts_with('/') {
-            // Ignore commands like /start for translation, but maybe handle /start specifically
-            if text == "/start" {
-                bot.send_message(msg.chat.id, "👋 Inline Translation Bot\nType @OukaroSUtslt_bot followed by text anywhere to translate between English and Chinese.\nYou can also send me text directly here!").await?;
+            // Unknown commands are ignored rather than translated
+            let Some((command, args)) = Command::parse(text) else {
+                return Ok(());
+            };
+            match command {
+                Command::Start => {
+                    // A deep link from the inline button carries the query.
+                    let handed_over = Some(args.trim())
+                        .filter(|key| !key.is_empty())
+                        .zip(msg.from.as_ref())
+                        .and_then(|(key, user)| stores.deep_links.take(key, user.id.0));
+                    let Some(query) = handed_over else {
+                        bot.send_message(msg.chat.id, i18n::text(lang, Msg::Start))
+                            .await?;
+                        return Ok(());
+                    };
+                    let Some(_slots) = take_slots(&bot, &stores, msg.chat.id, lang).await? else {
+                        return Ok(());
+                    };
+                    return translate_and_reply(
+                        &bot,
+                        &msg,
+                        &query,
+                        &translator,
+                        &config,
+                        tts,
+                        &stores,
+                    )
+                    .await;
+                }
+                Command::ForgetMe => {
+                    // History, verbosity, pending /tr prompts and handed-over
+                    // inline queries are what is kept per user in memory;
+                    // cached translations are keyed by text alone. The audit
+                    // log is an operator's record and is not rewritten.
+                    let forgotten = msg.from.as_ref().is_some_and(|user| {
+                        let history = stores.history.clear(user.id.0);
+                        let verbosity = stores.verbosity.clear(user.id.0);
+                        let pending = stores.pending.clear(user.id.0);
+                        let deep_links = stores.deep_links.clear(user.id.0);
+                        history || verbosity || pending || deep_links
+                    });
+                    let mut reply = i18n::text(
+                        lang,
+                        if forgotten {
+                            Msg::Forgotten
+                        } else {
+                            Msg::NothingToForget
+                        },
+                    )
+                    .to_string();
+                    if config.audit_log_path.is_some() {
+                        reply = format!("{}\n{}", reply, i18n::text(lang, Msg::AuditLogRetained));
+                    }
+                    bot.send_message(msg.chat.id, reply).await?;
+                }
+                Command::Verbosity => {
+                    let Some(user) = &msg.from else {
+                        return Ok(());
+                    };
+                    let reply = match args.trim().parse::<Verbosity>() {
+                        Ok(verbosity) => {
+                            stores.verbosity.set(user.id.0, verbosity);
+                            i18n::fill(
+                                i18n::text(lang, Msg::VerbositySet),
+                                &[&verbosity.to_string()],
+                            )
+                        }
+                        Err(_) => i18n::fill(
+                            i18n::text(lang, Msg::VerbosityCurrent),
+                            &[&stores.verbosity.get(user.id.0).to_string()],
+                        ),
+                    };
+                    bot.send_message(msg.chat.id, reply).await?;
+                }
+                Command::History => {
+                    let reply = match &msg.from {
+                        Some(user) => history_page(&stores.history, user.id.0, args, &config),
+                        None => return Ok(()),
+                    };
+                    send_long_message(&bot, msg.chat.id, &reply).await?;
+                }
+                Command::ClearHistory => {
+                    let reply = if !stores.history.is_enabled() {
+                        Msg::HistoryDisabled
+                    } else {
+                        if let Some(user) = &msg.from {
+                            stores.history.clear(user.id.0);
+                        }
+                        Msg::HistoryCleared
+                    };
+                    bot.send_message(msg.chat.id, i18n::text(lang, reply))
+                        .await?;
+                }
+                Command::Tr => {
+                    if !args.trim().is_empty() {
+                        let Some(_slots) = take_slots(&bot, &stores, msg.chat.id, lang).await?
+                        else {
+                            return Ok(());
+                        };
+                        return translate_and_reply(
+                            &bot,
+                            &msg,
+                            args,
+                            &translator,
+                            &config,
+                            tts,
+                            &stores,
+                        )
+                        .await;
+                    }
+                    if let Some(user) = &msg.from {
+                        stores.pending.insert(msg.chat.id.0, user.id.0);
+                        bot.send_message(msg.chat.id, i18n::text(lang, Msg::TranslatePrompt))
+                            .reply_parameters(ReplyParameters::new(msg.id))
+                            .reply_markup(ForceReply::new().selective())
+                            .await?;
+                    }
+                }
+                Command::Status => {
+                    let status = if config.provider_health_interval_secs == 0 {
+                        Msg::ProviderUnchecked
+                    } else if translator.is_healthy() {
+                        Msg::ProviderHealthy
+                    } else {
+                        Msg::ProviderUnhealthy
+                    };
+                    bot.send_message(msg.chat.id, i18n::text(lang, status))
+                        .await?;
+                }
+                Command::Version => {
+                    let mut reply = i18n::fill(
+                        i18n::text(lang, Msg::Version),
+                        &[env!("CARGO_PKG_VERSION"), env!("GIT_COMMIT")],
+                    );
+                    if msg
+                        .from
+                        .as_ref()
+                        .is_some_and(|user| config.is_admin(user.id.0))
+                    {
+                        let provider = i18n::fill(
+                            i18n::text(lang, Msg::VersionProvider),
+                            // The model follows `/reload`; the flavor needs a restart.
+                            &[&config.provider_flavor.to_string(), &translator.model()],
+                        );
+                        reply = format!("{}\n{}", reply, provider);
+                    }
+                    bot.send_message(msg.chat.id, reply).await?;
+                }
+                Command::Reload => {
+                    let is_operator = msg
+                        .from
+                        .as_ref()
+                        .is_some_and(|user| config.is_operator(user.id.0));
+                    let reply = if !is_operator {
+                        i18n::text(lang, Msg::AdminOnly).to_string()
+                    } else {
+                        // Values in .env replace those read at startup.
+                        match Config::reload_from_env() {
+                            Ok(reloaded) => {
+                                translator.reload(reloaded);
+                                i18n::text(lang, Msg::Reloaded).to_string()
+                            }
+                            Err(e) => {
+                                warn!("Rejected reloaded configuration: {}", e);
+                                i18n::fill(i18n::text(lang, Msg::ReloadFailed), &[&e.to_string()])
+                            }
+                        }
+                    };
+                    bot.send_message(msg.chat.id, reply).await?;
+                }
+                Command::Romanize => {
+                    if args.trim().is_empty() {
+                        bot.send_message(msg.chat.id, i18n::text(lang, Msg::RomanizeUsage))
+                            .await?;
+                        return Ok(());
+                    }
+                    let Some(_slots) = take_slots(&bot, &stores, msg.chat.id, lang).await? else {
+                        return Ok(());
+                    };
+                    let query = format!("romanize: {}", args);
+                    return translate_and_reply(
+                        &bot,
+                        &msg,
+                        &query,
+                        &translator,
+                        &config,
+                        tts,
+                        &stores,
+                    )
+                    .await;
+                }
+                Command::Explain => {
+                    if !config.explain_mode {
+                        bot.send_message(msg.chat.id, i18n::text(lang, Msg::ExplainDisabled))
+                            .await?;
+                        return Ok(());
+                    }
+                    if args.trim().is_empty() {
+                        bot.send_message(msg.chat.id, i18n::text(lang, Msg::ExplainUsage))
+                            .await?;
+                        return Ok(());
+                    }
+                    let Some(_slots) = take_slots(&bot, &stores, msg.chat.id, lang).await? else {
+                        return Ok(());
+                    };
+                    let query = format!("explain: {}", args);
+                    return translate_and_reply(
+                        &bot,
+                        &msg,
+                        &query,
+                        &translator,
+                        &config,
+                        tts,
+                        &stores,
+                    )
+                    .await;
+                }
+                Command::TrAll => {
+                    let sources = match msg.reply_to_message() {
+                        Some(replied) => match replied.media_group_id() {
+                            Some(group) => stores.albums.get(&group.0),
+                            None => replied
+                                .text()
+                                .or(replied.caption())
+                                .map(|text| vec![(replied.id.0, text.to_string())])
+                                .unwrap_or_default(),
+                        },
+                        None => Vec::new(),
+                    };
+                    if sources.is_empty() {
+                        bot.send_message(msg.chat.id, i18n::text(lang, Msg::TranslateAllUsage))
+                            .await?;
+                        return Ok(());
+                    }
+                    let Some(_slots) = take_slots(&bot, &stores, msg.chat.id, lang).await? else {
+                        return Ok(());
+                    };
+                    let user_id = msg.from.as_ref().map(|user| user.id.0);
+                    return translate_all_and_reply(
+                        &bot,
+                        msg.chat.id,
+                        sources,
+                        &translator,
+                        &config,
+                        &stores,
+                        user_id,
+                    )
+                    .await;
+                }
             }
             return Ok(());
         }
 
-        // Reuse inline parsing logic to detect language and normalize text
-        // We treat the message text exactly like an inline query input
-        let parsed = inline::parse_inline_query(
-            text,
-            config.default_source_lang,
-            config.default_target_lang,
+        // A reply to a /tr prompt clears the pending state; either way the
+        // text is translated like any other message.
+        if let Some(user) = &msg.from
+            && stores.pending.take(msg.chat.id.0, user.id.0)
+        {
+            info!("Translating pending /tr input in chat {}", msg.chat.id);
+        }
+
+        let Some(_slots) = take_slots(&bot, &stores, msg.chat.id, lang).await? else {
+            return Ok(());
+        };
+        translate_and_reply(&bot, &msg, text, &translator, &config, tts, &stores).await?;
+    }
+    Ok(())
+}
+
+/// Translates a post in a channel the bot administers, with
+/// `translate_channel_posts`. Commands and posts without text or a caption
+/// are skipped, as is anything that fails: a channel has no one to tell.
+async fn handle_channel_post(
+    bot: Bot,
+    msg: Message,
+    translator: Arc<Translator>,
+    config: Arc<Config>,
+    stores: Arc<Stores>,
+) -> ResponseResult<()> {
+    if !config.translate_channel_posts {
+        return Ok(());
+    }
+    let Some(text) = msg.text().or(msg.caption()) else {
+        return Ok(());
+    };
+    if text.starts_with('/') {
+        return Ok(());
+    }
+
+    let parsed = match inline::parse_inline_query(text, &config.parse_options()) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            debug!("Not translating channel post: {}", e);
+            return Ok(());
+        }
+    };
+
+    let Some(_update) = stores.updates.try_acquire() else {
+        warn!(
+            in_flight = stores.updates.in_flight(),
+            "Skipping channel post"
+        );
+        return Ok(());
+    };
+    let Some(_slot) = stores.chat_slots.try_acquire(msg.chat.id.0) else {
+        debug!("Skipping channel post in busy chat {}", msg.chat.id);
+        return Ok(());
+    };
+    let translation = match translator
+        .translate(TranslationRequest::from(&parsed))
+        .await
+    {
+        Ok(translation) => translation,
+        Err(e) => {
+            warn!(error = %e, chat_id = msg.chat.id.0, "Channel post translation failed");
+            return Ok(());
+        }
+    };
+    if translation.unchanged {
+        return Ok(());
+    }
+    audit(&stores, &config, None, &parsed).await;
+
+    let translated = format!(
+        "{}\n{}",
+        config.direction_header(parsed.source_lang, parsed.target_lang),
+        translation.primary_text
+    );
+    if config.channel_post_mode == ChannelPostMode::Edit {
+        // Appending leaves the offsets of the post's own formatting valid.
+        let appended = format!("{}\n\n{}", text, translated);
+        let length = appended.encode_utf16().count();
+        let edited = match (msg.text(), msg.caption()) {
+            (Some(_), _) if length <= MESSAGE_LIMIT => {
+                let mut edit = bot.edit_message_text(msg.chat.id, msg.id, appended);
+                edit.entities = msg.entities().map(<[_]>::to_vec);
+                Some(edit.await.map(|_| ()))
+            }
+            (None, Some(_)) if length <= CAPTION_LIMIT => {
+                let mut edit = bot
+                    .edit_message_caption(msg.chat.id, msg.id)
+                    .caption(appended);
+                edit.caption_entities = msg.caption_entities().map(<[_]>::to_vec);
+                Some(edit.await.map(|_| ()))
+            }
+            _ => None,
+        };
+        match edited {
+            Some(Ok(())) => return Ok(()),
+            Some(Err(e)) => warn!("Could not edit channel post, replying instead: {}", e),
+            None => debug!("Channel post too long to append to, replying instead"),
+        }
+    }
+
+    for chunk in split_for_telegram(&translated, MESSAGE_LIMIT) {
+        bot.send_message(msg.chat.id, chunk)
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+    }
+    Ok(())
+}
+
+/// Renders page `args` (1 when absent or invalid) of the user's history,
+/// newest first.
+fn history_page(history: &HistoryStore, user_id: u64, args: &str, config: &Config) -> String {
+    let lang = config.ui_language;
+    if !history.is_enabled() {
+        return i18n::text(lang, Msg::HistoryDisabled).to_string();
+    }
+    let entries = history.recent(user_id);
+    if entries.is_empty() {
+        return i18n::text(lang, Msg::HistoryEmpty).to_string();
+    }
+
+    let pages = entries.len().div_ceil(HISTORY_PAGE_SIZE);
+    let page = args.trim().parse::<usize>().unwrap_or(1).clamp(1, pages);
+    let lines = entries
+        .iter()
+        .enumerate()
+        .skip((page - 1) * HISTORY_PAGE_SIZE)
+        .take(HISTORY_PAGE_SIZE)
+        .map(|(index, entry)| {
+            format!(
+                "{}. {} ({} ago)\n“{}”\n{}",
+                index + 1,
+                config.direction_header(entry.source_lang, entry.target_lang),
+                format_age(entry.at.elapsed()),
+                truncate(&entry.text, 100),
+                entry.translation
+            )
+        })
+        .collect::<Vec<_>>();
+    format!(
+        "{}\n\n{}",
+        i18n::fill(
+            i18n::text(lang, Msg::HistoryPage),
+            &[&page.to_string(), &pages.to_string()]
+        ),
+        lines.join("\n\n")
+    )
+}
+
+/// Coarse age like "5m" or "3d".
+fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m", secs / 60),
+        3600..86400 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
+/// Appends a translation served to `user_id` to the audit log.
+async fn audit(stores: &Stores, config: &Config, user_id: Option<u64>, parsed: &ParsedInlineQuery) {
+    let entry = AuditEntry::new(
+        user_id,
+        &parsed.text,
+        parsed.source_lang,
+        parsed.target_lang,
+        config.audit_log_text,
+    );
+    stores.audit.record(entry).await;
+}
+
+/// Takes a bot-wide update slot and one of `chat_id`'s slots for a
+/// translation, telling the user when the bot or the chat is at its cap.
+/// Only taken once a message is known to need translating, so commands and
+/// messages that are skipped are never turned away.
+async fn take_slots<'a>(
+    bot: &Bot,
+    stores: &'a Stores,
+    chat_id: ChatId,
+    lang: UiLanguage,
+) -> ResponseResult<Option<(UpdateSlot<'a>, ChatSlot<'a>)>> {
+    let Some(update) = stores.updates.try_acquire() else {
+        warn!(
+            in_flight = stores.updates.in_flight(),
+            "Turning away message"
         );
+        bot.send_message(chat_id, i18n::text(lang, Msg::BotBusy))
+            .await?;
+        return Ok(None);
+    };
+    let Some(slot) = stores.chat_slots.try_acquire(chat_id.0) else {
+        bot.send_message(chat_id, i18n::text(lang, Msg::ChatBusy))
+            .await?;
+        return Ok(None);
+    };
+    Ok(Some((update, slot)))
+}
 
-        if let Some(parsed_query) = parsed {
-            // Send a "typing" action
-            let _ = bot
-                .send_chat_action(msg.chat.id, teloxide::types::ChatAction::Typing)
-                .await;
-
-            match translator
-                .translate(crate::types::TranslationRequest {
-                    text: parsed_query.text.clone(),
-                    source_lang: parsed_query.source_lang,
-                    target_lang: parsed_query.target_lang,
-                })
-                .await
-            {
-                Ok(translation) => {
-                    let response = format!(
-                        "🌐 {} → {}\n\n{}",
-                        parsed_query.source_lang.to_string().to_uppercase(),
-                        parsed_query.target_lang.to_string().to_uppercase(),
-                        translation.primary_text
-                    );
+/// Translates several messages concurrently and replies with one numbered
+/// list, each entry opening with a snippet of the message it came from.
+async fn translate_all_and_reply(
+    bot: &Bot,
+    chat_id: ChatId,
+    sources: Vec<(i32, String)>,
+    translator: &Arc<Translator>,
+    config: &Config,
+    stores: &Stores,
+    user_id: Option<u64>,
+) -> ResponseResult<()> {
+    let _ = bot
+        .send_chat_action(chat_id, teloxide::types::ChatAction::Typing)
+        .await;
 
-                    bot.send_message(msg.chat.id, response).await?;
+    let mut tasks = JoinSet::new();
+    for (index, (_, text)) in sources.iter().enumerate() {
+        let parsed = inline::parse_inline_query(text, &config.parse_options());
+        let translator = Arc::clone(translator);
+        let detail = config.user_error_detail;
+        tasks.spawn(async move {
+            let outcome = match parsed {
+                Ok(parsed) => translator
+                    .translate(TranslationRequest::from(&parsed))
+                    .await
+                    .map(|translation| (parsed, translation))
+                    .map_err(|e| failure_message(&e, detail)),
+                Err(e) => Err(e.to_string()),
+            };
+            (index, outcome)
+        });
+    }
 
-                    if let Some(romanized) = translation.romanized_text {
-                        bot.send_message(msg.chat.id, format!("Romanized:\n{}", romanized))
-                            .await?;
-                    }
+    let mut entries = vec![String::new(); sources.len()];
+    while let Some(joined) = tasks.join_next().await {
+        let Ok((index, outcome)) = joined else {
+            continue;
+        };
+        let snippet = truncate(&sources[index].1, 40);
+        if let Ok((parsed, _)) = &outcome {
+            audit(stores, config, user_id, parsed).await;
+        }
+        let body = match outcome {
+            Ok((parsed, translation)) => format!(
+                "{}\n{}",
+                config.direction_header(parsed.source_lang, parsed.target_lang),
+                translation.primary_text
+            ),
+            Err(message) => i18n::fill(
+                i18n::text(config.ui_language, Msg::TranslationFailed),
+                &[&message],
+            ),
+        };
+        entries[index] = format!("{}. “{}”\n{}", index + 1, snippet, body);
+    }
+
+    send_long_message(bot, chat_id, &entries.join("\n\n")).await
+}
+
+/// Reads the text in the largest size of a photo and translates it.
+async fn translate_photo(
+    bot: &Bot,
+    msg: &Message,
+    ocr: &OcrClient,
+    translator: &Translator,
+    config: &Config,
+    tts: Option<&TtsClient>,
+    stores: &Stores,
+) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let Some(largest) = msg
+        .photo()
+        .and_then(|photos| photos.iter().max_by_key(|p| p.width * p.height))
+    else {
+        return Ok(());
+    };
+
+    let _ = bot
+        .send_chat_action(chat_id, teloxide::types::ChatAction::Typing)
+        .await;
+
+    let file = bot.get_file(largest.file.id.clone()).await?;
+    let mut image = Vec::new();
+    bot.download_file(&file.path, &mut image).await?;
+
+    match ocr.extract_text(image).await {
+        Ok(text) if !text.is_empty() => {
+            translate_and_reply(bot, msg, &text, translator, config, tts, stores).await
+        }
+        Ok(_) => {
+            bot.send_message(chat_id, i18n::text(config.ui_language, Msg::NoTextInImage))
+                .await?;
+            Ok(())
+        }
+        Err(e) => {
+            warn!("OCR failed: {}", e);
+            bot.send_message(chat_id, i18n::text(config.ui_language, Msg::OcrFailed))
+                .await?;
+            Ok(())
+        }
+    }
+}
+
+/// Parses `text` like an inline query, translates it and replies in the
+/// chat of `msg`, recording the translation in its sender's history.
+async fn translate_and_reply(
+    bot: &Bot,
+    msg: &Message,
+    text: &str,
+    translator: &Translator,
+    config: &Config,
+    tts: Option<&TtsClient>,
+    stores: &Stores,
+) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    // Reuse inline parsing logic to detect language and normalize text
+    // We treat the message text exactly like an inline query input
+    let parsed = inline::parse_inline_query(text, &config.parse_options());
+
+    let parsed_query = match parsed {
+        Ok(parsed_query) => parsed_query,
+        Err(ParseError::Empty) => {
+            bot.send_message(
+                chat_id,
+                i18n::text(config.ui_language, Msg::CouldNotUnderstand),
+            )
+            .await?;
+            return Ok(());
+        }
+        Err(e) => {
+            let message = i18n::fill(
+                i18n::text(config.ui_language, Msg::TranslationFailed),
+                &[&e.to_string()],
+            );
+            bot.send_message(chat_id, message).await?;
+            return Ok(());
+        }
+    };
+
+    // Send a "typing" action
+    let _ = bot
+        .send_chat_action(chat_id, teloxide::types::ChatAction::Typing)
+        .await;
+
+    let verbosity = msg
+        .from
+        .as_ref()
+        .map_or_else(Verbosity::default, |user| stores.verbosity.get(user.id.0));
+    let show_alternatives = match verbosity {
+        Verbosity::Terse => false,
+        Verbosity::Normal => config.dm_show_alternatives,
+        Verbosity::Rich => true,
+    };
+
+    let mut request = TranslationRequest::from(&parsed_query);
+    if show_alternatives && parsed_query.alternative_count.is_none() {
+        request.alternatives = DM_ALTERNATIVES;
+    }
+    let max_alternatives = request.alternatives;
+
+    let outcome = translator.translate(request).await;
+    if outcome.is_ok() {
+        let user_id = msg.from.as_ref().map(|user| user.id.0);
+        audit(stores, config, user_id, &parsed_query).await;
+    }
+    // Only a message's own text can be translated again from the button.
+    let suggest_swap = outcome.is_ok()
+        && !parsed_query.romanize_only
+        && msg.chat.is_private()
+        && msg.text() == Some(text)
+        && parsed_query
+            .detection_confidence
+            .is_some_and(|confidence| confidence < config.swap_suggestion_confidence);
+    if let (Ok(translation), Some(user)) = (&outcome, &msg.from) {
+        stores.history.record(
+            user.id.0,
+            &parsed_query.text,
+            parsed_query.source_lang,
+            parsed_query.target_lang,
+            translation
+                .romanized_text
+                .as_ref()
+                .filter(|_| parsed_query.romanize_only)
+                .unwrap_or(&translation.primary_text),
+        );
+    }
+    match outcome {
+        Ok(translation) if parsed_query.romanize_only => {
+            let romanized = translation
+                .romanized_text
+                .unwrap_or(translation.primary_text);
+            send_long_message(bot, chat_id, &romanized).await?;
+        }
+        // An explanation was asked for explicitly, so even terse output keeps it.
+        Ok(translation) if verbosity == Verbosity::Terse && translation.explanation.is_none() => {
+            send_long_message(bot, chat_id, &translation.primary_text).await?;
+        }
+        Ok(translation) => {
+            let alternatives = if show_alternatives {
+                numbered_alternatives(
+                    &translation.primary_text,
+                    &translation.alternate_texts,
+                    max_alternatives,
+                )
+            } else {
+                None
+            };
+            let alternatives_label = i18n::text(config.ui_language, Msg::AlternativesLabel);
+            let explanation_label = i18n::text(config.ui_language, Msg::ExplanationLabel);
+
+            let spoken = tts
+                .filter(|_| config.tts_languages.contains(&parsed_query.target_lang))
+                .map(|tts| (tts, translation.primary_text.clone()));
+
+            let header =
+                config.direction_header(parsed_query.source_lang, parsed_query.target_lang);
+            let primary_text = if translation.unchanged {
+                let note = i18n::text(config.ui_language, Msg::Unchanged);
+                format!("{}\n{}", translation.primary_text, note)
+            } else {
+                translation.primary_text
+            };
+            let response = format!("{}\n\n{}", header, primary_text);
+
+            let scheme = parsed_query
+                .target_lang
+                .romanization_scheme()
+                .unwrap_or("Romanized");
+            let scheme_label = if translation.romanized_is_fallback {
+                format!("{} (approx.)", scheme)
+            } else {
+                scheme.to_string()
+            };
+            if config.quote_translations {
+                let mut sections = vec![(header, primary_text)];
+                if let Some(romanized) = translation.romanized_text {
+                    sections.push((format!("{}:", scheme_label), romanized));
                 }
-                Err(e) => {
-                    bot.send_message(msg.chat.id, format!("⚠️ Translation failed: {}", e))
+                if let Some(alternatives) = alternatives {
+                    sections.push((alternatives_label.to_string(), alternatives));
+                }
+                if let Some(explanation) = translation.explanation {
+                    sections.push((explanation_label.to_string(), explanation));
+                }
+                send_quoted_sections(bot, chat_id, &sections, config.compact_dm).await?;
+            } else if config.compact_dm {
+                let response = match translation.romanized_text {
+                    Some(romanized) => {
+                        format!("{}\n\n{}:\n{}", response, scheme_label, romanized)
+                    }
+                    None => response,
+                };
+                let response = match alternatives {
+                    Some(alternatives) => {
+                        format!("{}\n\n{}\n{}", response, alternatives_label, alternatives)
+                    }
+                    None => response,
+                };
+                let response = match translation.explanation {
+                    Some(explanation) => {
+                        format!("{}\n\n{}\n{}", response, explanation_label, explanation)
+                    }
+                    None => response,
+                };
+                send_long_message(bot, chat_id, &response).await?;
+            } else {
+                send_long_message(bot, chat_id, &response).await?;
+
+                if let Some(romanized) = translation.romanized_text {
+                    send_long_message(bot, chat_id, &format!("{}:\n{}", scheme_label, romanized))
                         .await?;
                 }
+                if let Some(alternatives) = alternatives {
+                    send_long_message(
+                        bot,
+                        chat_id,
+                        &format!("{}\n{}", alternatives_label, alternatives),
+                    )
+                    .await?;
+                }
+                if let Some(explanation) = translation.explanation {
+                    send_long_message(
+                        bot,
+                        chat_id,
+                        &format!("{}\n{}", explanation_label, explanation),
+                    )
+                    .await?;
+                }
             }
-        } else {
-            bot.send_message(
-                msg.chat.id,
-                "Could not understand the input. Please try again.",
-            )
+
+            // Audio is a bonus: failures are logged and the text stands alone.
+            if let Some((tts, text)) = spoken {
+                match tts.synthesize(&text).await {
+                    Ok(audio) => {
+                        if let Err(e) = bot.send_voice(chat_id, InputFile::memory(audio)).await {
+                            warn!("Failed to send voice message: {}", e);
+                        }
+                    }
+                    Err(e) => warn!("TTS failed, skipping voice message: {}", e),
+                }
+            }
+        }
+        Err(e) => {
+            let message = i18n::fill(
+                i18n::text(config.ui_language, Msg::TranslationFailed),
+                &[&failure_message(&e, config.user_error_detail)],
+            );
+            bot.send_message(chat_id, message).await?;
+        }
+    }
+
+    if suggest_swap {
+        let header = config.direction_header(parsed_query.source_lang, parsed_query.target_lang);
+        let button = InlineKeyboardButton::callback(
+            i18n::text(config.ui_language, Msg::SwapDirection),
+            SWAP_DIRECTION_CALLBACK,
+        );
+        bot.send_message(
+            chat_id,
+            i18n::fill(
+                i18n::text(config.ui_language, Msg::DirectionUnsure),
+                &[&header],
+            ),
+        )
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .reply_markup(InlineKeyboardMarkup::new([[button]]))
+        .await?;
+    }
+    Ok(())
+}
+
+/// What users are told about a failed translation. The full error is
+/// logged first, since `detail` may hide it from them.
+fn failure_message(error: &TranslationError, detail: ErrorDetail) -> String {
+    warn!(error = %error, "Translation failed");
+    error.user_message(detail)
+}
+
+/// Numbers up to `max` alternatives, one per line, skipping repeats and any
+/// that match the primary translation. `None` when none are left.
+fn numbered_alternatives(primary: &str, alternates: &[String], max: usize) -> Option<String> {
+    let mut seen = vec![primary.trim()];
+    let mut lines = Vec::new();
+    for alternate in alternates.iter().map(|s| s.trim()) {
+        if lines.len() >= max {
+            break;
+        }
+        if alternate.is_empty() || seen.contains(&alternate) {
+            continue;
+        }
+        seen.push(alternate);
+        lines.push(format!("{}. {}", lines.len() + 1, alternate));
+    }
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+/// Sends `text` as one or more messages, splitting it to fit Telegram's limit.
+async fn send_long_message(bot: &Bot, chat_id: ChatId, text: &str) -> ResponseResult<()> {
+    for chunk in split_for_telegram(text, MESSAGE_LIMIT) {
+        bot.send_message(chat_id, chunk).await?;
+    }
+    Ok(())
+}
+
+/// Sends `(label, body)` sections with each body in an HTML block quote. In
+/// compact mode they share one message when it fits; otherwise every section
+/// is sent (and if needed split) on its own, with the label outside the quote.
+async fn send_quoted_sections(
+    bot: &Bot,
+    chat_id: ChatId,
+    sections: &[(String, String)],
+    compact: bool,
+) -> ResponseResult<()> {
+    let render = |label: &str, body: &str| {
+        format!(
+            "{}\n<blockquote>{}</blockquote>",
+            escape_html(label),
+            escape_html(body)
+        )
+    };
+    let utf16_len = |s: &str| s.encode_utf16().count();
+
+    let total: usize = sections
+        .iter()
+        .map(|(label, body)| utf16_len(label) + utf16_len(body) + 2)
+        .sum();
+    if compact && total <= MESSAGE_LIMIT {
+        let html = sections
+            .iter()
+            .map(|(label, body)| render(label, body))
+            .collect::<Vec<_>>()
+            .join("\n");
+        bot.send_message(chat_id, html)
+            .parse_mode(ParseMode::Html)
             .await?;
+        return Ok(());
+    }
+
+    for (label, body) in sections {
+        let budget = MESSAGE_LIMIT.saturating_sub(utf16_len(label) + 1).max(1);
+        for (index, chunk) in split_for_telegram(body, budget).iter().enumerate() {
+            let html = if index == 0 {
+                render(label, chunk)
+            } else {
+                format!("<blockquote>{}</blockquote>", escape_html(chunk))
+            };
+            bot.send_message(chat_id, html)
+                .parse_mode(ParseMode::Html)
+                .await?;
         }
     }
     Ok(())
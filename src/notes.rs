@@ -0,0 +1,91 @@
+//! Removal of notes some models tack onto a translation, like
+//! "(Note: this is a literal translation)".
+
+use regex::Regex;
+
+/// Notes recognised out of the box. Each must match a whole note; it is
+/// only removed when it ends the text.
+const BUILT_IN_PATTERNS: &[&str] = &[
+    r"(?i)\(\s*(?:note|n\.b\.|translator'?s?\s+note)\s*:[^()]*\)",
+    r"(?i)\(\s*(?:translated|translation)\s+(?:from|by|with|using)\b[^()]*\)",
+    r"(?i)\*+\s*(?:note\s*:|(?:translated|translation)\s+(?:from|by|with|using)\b)[^*]*\*+",
+    r"[（(]\s*(?:注|译注|注释|说明)\s*[:：][^()（）]*[）)]",
+    r"(?im)^\s*(?:note|translator'?s?\s+note)\s*:.*$",
+];
+
+/// Strips trailing translator notes. Conservative by design: a note is only
+/// removed at the very end of the text, so parentheticals inside the
+/// translation stay, and text that is nothing but a note is left alone.
+pub struct NoteStripper {
+    patterns: Vec<Regex>,
+}
+
+impl NoteStripper {
+    /// The built-in patterns plus `custom` ones.
+    pub fn new(custom: &[Regex]) -> Self {
+        let patterns = BUILT_IN_PATTERNS
+            .iter()
+            .map(|pattern| Regex::new(pattern).unwrap())
+            .chain(custom.iter().cloned())
+            .collect();
+        Self { patterns }
+    }
+
+    pub fn strip(&self, text: &str) -> String {
+        let mut kept = text.trim_end();
+        // Notes may come in pairs, e.g. "(Note: …) *Translated from English*"
+        while let Some(start) = self.trailing_note(kept) {
+            let rest = kept[..start].trim_end();
+            if rest.is_empty() {
+                break;
+            }
+            kept = rest;
+        }
+        kept.to_string()
+    }
+
+    /// Start of a note that runs to the end of `text`.
+    fn trailing_note(&self, text: &str) -> Option<usize> {
+        self.patterns
+            .iter()
+            .flat_map(|pattern| pattern.find_iter(text))
+            .filter(|m| m.end() == text.len())
+            .map(|m| m.start())
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trailing_notes_are_removed() {
+        let stripper = NoteStripper::new(&[]);
+        assert_eq!(
+            stripper.strip("你好，世界 (translated by AI)"),
+            "你好，世界"
+        );
+        assert_eq!(
+            stripper.strip("Hello (Note: literal translation) *Translated from Chinese*"),
+            "Hello"
+        );
+    }
+
+    #[test]
+    fn genuine_parentheticals_are_kept() {
+        let stripper = NoteStripper::new(&[]);
+        let text = "The meeting (moved from Monday) is at noon (Beijing time)";
+        assert_eq!(stripper.strip(text), text);
+        assert_eq!(
+            stripper.strip("(Note: nothing else here)"),
+            "(Note: nothing else here)"
+        );
+    }
+
+    #[test]
+    fn custom_patterns_are_stripped_too() {
+        let stripper = NoteStripper::new(&[Regex::new(r"\[MT\]").unwrap()]);
+        assert_eq!(stripper.strip("Good morning [MT]"), "Good morning");
+    }
+}
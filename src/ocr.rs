@@ -0,0 +1,60 @@
+use crate::config::Config;
+use anyhow::{Context, Result};
+use reqwest::Client;
+use reqwest::header::CONTENT_TYPE;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Client for an OCR endpoint that accepts raw image bytes and replies with
+/// `{"text": "..."}`.
+pub struct OcrClient {
+    client: Client,
+    url: String,
+    api_key: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OcrResponse {
+    text: String,
+}
+
+impl OcrClient {
+    /// Returns `None` when no OCR endpoint is configured.
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        let Some(url) = config.ocr_api_url.clone() else {
+            return Ok(None);
+        };
+
+        let client = Client::builder()
+            .timeout(Duration::from_millis(config.http_timeout_ms))
+            .build()?;
+
+        Ok(Some(Self {
+            client,
+            url,
+            api_key: config.ocr_api_key.clone(),
+        }))
+    }
+
+    /// Extracts the text from a JPEG image (the format Telegram stores photos in).
+    pub async fn extract_text(&self, image: Vec<u8>) -> Result<String> {
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header(CONTENT_TYPE, "image/jpeg")
+            .body(image);
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+        let response = request.send().await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            anyhow::bail!("OCR provider failed ({}): {}", status, text);
+        }
+
+        let parsed: OcrResponse = response.json().await.context("OCR response missing text")?;
+        Ok(parsed.text.trim().to_string())
+    }
+}
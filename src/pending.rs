@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Users who were prompted for text (via `/tr`) and whose next message in
+/// that chat should be translated. Entries expire after `timeout`.
+pub struct PendingInputs {
+    timeout: Duration,
+    entries: Mutex<HashMap<(i64, u64), Instant>>,
+}
+
+impl PendingInputs {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn insert(&self, chat_id: i64, user_id: u64) {
+        let mut entries = self.entries.lock().unwrap();
+        let timeout = self.timeout;
+        entries.retain(|_, since| since.elapsed() < timeout);
+        entries.insert((chat_id, user_id), Instant::now());
+    }
+
+    /// Removes the user's pending state, returning whether it was still live.
+    pub fn take(&self, chat_id: i64, user_id: u64) -> bool {
+        self.entries
+            .lock()
+            .unwrap()
+            .remove(&(chat_id, user_id))
+            .is_some_and(|since| since.elapsed() < self.timeout)
+    }
+
+    /// Removes the user's pending state in every chat, returning whether
+    /// there was any.
+    pub fn clear(&self, user_id: u64) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.len();
+        entries.retain(|(_, user), _| *user != user_id);
+        entries.len() < before
+    }
+}
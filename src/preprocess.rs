@@ -0,0 +1,595 @@
+//! Reversible text transformations applied around the provider call.
+
+use crate::config::{Config, EmojiPolicy, ProperNounPolicy};
+use crate::inline::core::{Script, dominant_script};
+use crate::protect::{protect_entities, protect_ranges, restore_entities};
+use crate::types::MixedScriptPolicy;
+use regex::Regex;
+
+/// What a [`Preprocessor`] needs to undo its changes after translation.
+#[derive(Debug, Clone, Default)]
+pub struct Context {
+    /// Original text of each placeholder, in placeholder order.
+    pub spans: Vec<String>,
+}
+
+/// Transforms input before translation and restores the output afterwards.
+pub trait Preprocessor: Send + Sync {
+    fn pre(&self, text: &str) -> (String, Context);
+    fn post(&self, text: &str, ctx: &Context) -> String;
+}
+
+/// Preprocessors run in order before translation and in reverse order after,
+/// so each `post` sees exactly what its own `pre` produced.
+#[derive(Default)]
+pub struct Pipeline {
+    stages: Vec<Box<dyn Preprocessor>>,
+}
+
+impl Pipeline {
+    pub fn new(stages: Vec<Box<dyn Preprocessor>>) -> Self {
+        Self { stages }
+    }
+
+    /// Built-in stages enabled by `config`.
+    pub fn from_config(config: &Config) -> Self {
+        let mut stages: Vec<Box<dyn Preprocessor>> = Vec::new();
+        // First, so that no other stage touches the code.
+        if config.protect_code_blocks {
+            stages.push(Box::new(CodeBlockProtect::new(
+                config.translate_code_comments,
+            )));
+        }
+        if config.collapse_whitespace {
+            stages.push(Box::new(WhitespaceCollapse));
+        }
+        if config.protect_urls {
+            stages.push(Box::new(UrlProtect::new()));
+        }
+        match config.emoji_policy {
+            EmojiPolicy::Preserve => stages.push(Box::new(EmojiProtect::new())),
+            EmojiPolicy::Strip => stages.push(Box::new(EmojiStrip::new())),
+            EmojiPolicy::Translate => {}
+        }
+        if config.protect_numerics {
+            stages.push(Box::new(NumericProtect::new()));
+        }
+        if config.mixed_script_policy == MixedScriptPolicy::Protect {
+            stages.push(Box::new(MixedScriptProtect::new()));
+        }
+        if config.proper_noun_policy == ProperNounPolicy::Keep && config.protect_proper_nouns {
+            stages.push(Box::new(ProperNounProtect::new()));
+        }
+        if !config.no_translate_patterns.is_empty() {
+            stages.push(Box::new(PatternProtect::new(
+                config.no_translate_patterns.clone(),
+            )));
+        }
+        Self::new(stages)
+    }
+
+    pub fn pre(&self, text: &str) -> (String, Vec<Context>) {
+        let mut contexts = Vec::with_capacity(self.stages.len());
+        let mut current = text.to_string();
+        for stage in &self.stages {
+            let (next, ctx) = stage.pre(&current);
+            current = next;
+            contexts.push(ctx);
+        }
+        (current, contexts)
+    }
+
+    pub fn post(&self, text: &str, contexts: &[Context]) -> String {
+        self.stages
+            .iter()
+            .zip(contexts)
+            .rev()
+            .fold(text.to_string(), |current, (stage, ctx)| {
+                stage.post(&current, ctx)
+            })
+    }
+}
+
+/// Collapses runs of spaces and tabs; there is nothing to restore.
+pub struct WhitespaceCollapse;
+
+impl Preprocessor for WhitespaceCollapse {
+    fn pre(&self, text: &str) -> (String, Context) {
+        let collapsed = text
+            .lines()
+            .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect::<Vec<_>>()
+            .join("\n");
+        (collapsed, Context::default())
+    }
+
+    fn post(&self, text: &str, _ctx: &Context) -> String {
+        text.to_string()
+    }
+}
+
+/// Swaps spans matching a set of patterns for tagged placeholders.
+pub struct PatternProtect {
+    patterns: Vec<Regex>,
+    tag: &'static str,
+}
+
+impl PatternProtect {
+    pub fn new(patterns: Vec<Regex>) -> Self {
+        Self { patterns, tag: "P" }
+    }
+}
+
+impl Preprocessor for PatternProtect {
+    fn pre(&self, text: &str) -> (String, Context) {
+        let protected = protect_entities(text, &self.patterns, self.tag);
+        (
+            protected.text,
+            Context {
+                spans: protected.spans,
+            },
+        )
+    }
+
+    fn post(&self, text: &str, ctx: &Context) -> String {
+        restore_entities(text, &ctx.spans, self.tag)
+    }
+}
+
+/// Keeps links intact: models like to "translate" paths and query strings.
+pub struct UrlProtect(PatternProtect);
+
+impl UrlProtect {
+    pub fn new() -> Self {
+        let url = Regex::new(r"(?i)\b(?:https?://|www\.)[^\s<>]+[^\s<>.,;:!?)\]'“”]").unwrap();
+        Self(PatternProtect {
+            patterns: vec![url],
+            tag: "U",
+        })
+    }
+}
+
+impl Default for UrlProtect {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Preprocessor for UrlProtect {
+    fn pre(&self, text: &str) -> (String, Context) {
+        self.0.pre(text)
+    }
+
+    fn post(&self, text: &str, ctx: &Context) -> String {
+        self.0.post(text, ctx)
+    }
+}
+
+/// Keeps numbers, amounts and times exactly as written, e.g. "$1,000" or
+/// "3:30". Word boundaries keep it out of placeholders like `⟦U0⟧`.
+pub struct NumericProtect(PatternProtect);
+
+impl NumericProtect {
+    pub fn new() -> Self {
+        let number = Regex::new(r"[$€£¥]?\b\d+(?:[.,:]\d+)*%?").unwrap();
+        Self(PatternProtect {
+            patterns: vec![number],
+            tag: "N",
+        })
+    }
+}
+
+impl Default for NumericProtect {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Preprocessor for NumericProtect {
+    fn pre(&self, text: &str) -> (String, Context) {
+        self.0.pre(text)
+    }
+
+    fn post(&self, text: &str, ctx: &Context) -> String {
+        self.0.post(text, ctx)
+    }
+}
+
+/// An emoji with its optional variation selector and skin tone, including
+/// ZWJ sequences, or a flag (a pair of regional indicators).
+fn emoji_regex() -> Regex {
+    Regex::new(
+        r"\p{Extended_Pictographic}[\x{FE0F}\p{Emoji_Modifier}]?(?:\x{200D}\p{Extended_Pictographic}[\x{FE0F}\p{Emoji_Modifier}]?)*|\p{Regional_Indicator}{2}",
+    )
+    .unwrap()
+}
+
+/// Keeps emoji exactly as sent by swapping them for placeholders.
+pub struct EmojiProtect(PatternProtect);
+
+impl EmojiProtect {
+    pub fn new() -> Self {
+        Self(PatternProtect {
+            patterns: vec![emoji_regex()],
+            tag: "E",
+        })
+    }
+}
+
+impl Default for EmojiProtect {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Preprocessor for EmojiProtect {
+    fn pre(&self, text: &str) -> (String, Context) {
+        self.0.pre(text)
+    }
+
+    fn post(&self, text: &str, ctx: &Context) -> String {
+        self.0.post(text, ctx)
+    }
+}
+
+/// Removes emoji; they are not restored afterwards.
+pub struct EmojiStrip(Regex);
+
+impl EmojiStrip {
+    pub fn new() -> Self {
+        Self(emoji_regex())
+    }
+}
+
+impl Default for EmojiStrip {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Preprocessor for EmojiStrip {
+    fn pre(&self, text: &str) -> (String, Context) {
+        let stripped = self.0.replace_all(text, "");
+        (stripped.trim().to_string(), Context::default())
+    }
+
+    fn post(&self, text: &str, _ctx: &Context) -> String {
+        text.to_string()
+    }
+}
+
+/// Keeps runs of the less common script as they are: English terms in
+/// Chinese text, or Chinese words in English text.
+pub struct MixedScriptProtect {
+    latin_run: Regex,
+    han_run: Regex,
+    placeholder: Regex,
+}
+
+impl MixedScriptProtect {
+    pub fn new() -> Self {
+        Self {
+            latin_run: Regex::new(
+                r"[A-Za-z][A-Za-z0-9'’+#-]*(?:[ \t]+[A-Za-z0-9][A-Za-z0-9'’+#-]*)*",
+            )
+            .unwrap(),
+            han_run: Regex::new(r"\p{Han}+").unwrap(),
+            placeholder: Regex::new(r"⟦[^⟧]*⟧").unwrap(),
+        }
+    }
+}
+
+impl Default for MixedScriptProtect {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Preprocessor for MixedScriptProtect {
+    fn pre(&self, text: &str) -> (String, Context) {
+        let runs = match dominant_script(&self.placeholder.replace_all(text, "")) {
+            Script::Cjk => &self.latin_run,
+            Script::Latin => &self.han_run,
+            _ => return (text.to_string(), Context::default()),
+        };
+        // Earlier stages' placeholders contain Latin letters; they neither
+        // count towards the script balance nor get protected again.
+        let placeholders: Vec<_> = self
+            .placeholder
+            .find_iter(text)
+            .map(|m| m.range())
+            .collect();
+        let matches = runs
+            .find_iter(text)
+            .map(|m| (m.start(), m.end()))
+            .filter(|(start, end)| {
+                !placeholders
+                    .iter()
+                    .any(|range| *start < range.end && range.start < *end)
+            })
+            .collect();
+        let protected = protect_ranges(text, matches, "M");
+        (
+            protected.text,
+            Context {
+                spans: protected.spans,
+            },
+        )
+    }
+
+    fn post(&self, text: &str, ctx: &Context) -> String {
+        restore_entities(text, &ctx.spans, "M")
+    }
+}
+
+/// Keeps likely proper nouns as written: runs of capitalized words, such as
+/// "New York" or "OpenAI", except a lone capitalized word opening a
+/// sentence, which is usually just capitalized for that reason. Single
+/// letters like "I" never count.
+pub struct ProperNounProtect {
+    name: Regex,
+    placeholder: Regex,
+}
+
+impl ProperNounProtect {
+    pub fn new() -> Self {
+        Self {
+            name: Regex::new(r"\b\p{Lu}[\p{L}\p{N}'’&-]+(?:[ \t]+\p{Lu}[\p{L}\p{N}'’&-]+)*")
+                .unwrap(),
+            placeholder: Regex::new(r"⟦[^⟧]*⟧").unwrap(),
+        }
+    }
+}
+
+impl Default for ProperNounProtect {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Preprocessor for ProperNounProtect {
+    fn pre(&self, text: &str) -> (String, Context) {
+        let placeholders: Vec<_> = self
+            .placeholder
+            .find_iter(text)
+            .map(|m| m.range())
+            .collect();
+        let matches = self
+            .name
+            .find_iter(text)
+            .filter(|m| {
+                let before = &text[..m.start()];
+                let previous = before.trim_end();
+                let opens_sentence = before[previous.len()..].contains('\n')
+                    || previous
+                        .chars()
+                        .last()
+                        .is_none_or(|c| matches!(c, '.' | '!' | '?' | ':' | '"' | '“'));
+                let lone = !m.as_str().contains(char::is_whitespace);
+                !(lone && opens_sentence)
+            })
+            .map(|m| (m.start(), m.end()))
+            .filter(|(start, end)| {
+                !placeholders
+                    .iter()
+                    .any(|range| *start < range.end && range.start < *end)
+            })
+            .collect();
+        let protected = protect_ranges(text, matches, "C");
+        (
+            protected.text,
+            Context {
+                spans: protected.spans,
+            },
+        )
+    }
+
+    fn post(&self, text: &str, ctx: &Context) -> String {
+        restore_entities(text, &ctx.spans, "C")
+    }
+}
+
+/// Keeps ``` fenced code byte for byte, fences included. With
+/// `translate_comments`, the text of `//` and `#` comments is left out of
+/// the placeholders so it is translated along with the prose.
+pub struct CodeBlockProtect {
+    block: Regex,
+    comment: Regex,
+    translate_comments: bool,
+}
+
+impl CodeBlockProtect {
+    pub fn new(translate_comments: bool) -> Self {
+        Self {
+            block: Regex::new(r"(?s)```.*?```").unwrap(),
+            comment: Regex::new(r"(?m)(?:(?:^|[ \t])//|^[ \t]*#)[ \t]*").unwrap(),
+            translate_comments,
+        }
+    }
+
+    /// Ranges of the block at `start..end` to keep, leaving out comment text.
+    fn code_ranges(&self, text: &str, start: usize, end: usize) -> Vec<(usize, usize)> {
+        let mut ranges = Vec::new();
+        let mut cursor = start;
+        for marker in self.comment.find_iter(&text[start..end]) {
+            let comment_start = start + marker.end();
+            if comment_start <= cursor {
+                continue;
+            }
+            let comment_end = text[comment_start..end]
+                .find('\n')
+                .map_or(end, |offset| comment_start + offset);
+            if comment_end > comment_start && comment_end < end {
+                ranges.push((cursor, comment_start));
+                cursor = comment_end;
+            }
+        }
+        ranges.push((cursor, end));
+        ranges
+    }
+}
+
+impl Preprocessor for CodeBlockProtect {
+    fn pre(&self, text: &str) -> (String, Context) {
+        let matches = self
+            .block
+            .find_iter(text)
+            .flat_map(|m| {
+                if self.translate_comments {
+                    self.code_ranges(text, m.start(), m.end())
+                } else {
+                    vec![(m.start(), m.end())]
+                }
+            })
+            .collect();
+        let protected = protect_ranges(text, matches, "K");
+        (
+            protected.text,
+            Context {
+                spans: protected.spans,
+            },
+        )
+    }
+
+    fn post(&self, text: &str, ctx: &Context) -> String {
+        restore_entities(text, &ctx.spans, "K")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs `text` through `stage` and back, with `translate` standing in
+    /// for the provider.
+    fn round_trip(
+        stage: &dyn Preprocessor,
+        text: &str,
+        translate: impl Fn(&str) -> String,
+    ) -> (String, String) {
+        let (sent, ctx) = stage.pre(text);
+        let restored = stage.post(&translate(&sent), &ctx);
+        (sent, restored)
+    }
+
+    #[test]
+    fn no_translate_patterns_survive_translation() {
+        let stage = PatternProtect::new(vec![Regex::new(r"\bERR_[A-Z_]+\b").unwrap()]);
+        let (sent, restored) = round_trip(&stage, "Got ERR_NO_AUTH and ERR_TIMEOUT again", |s| {
+            s.replace("Got", "收到")
+                .replace("and", "和")
+                .replace("again", "又一次")
+        });
+        assert_eq!(sent, "Got ⟦P0⟧ and ⟦P1⟧ again");
+        assert_eq!(restored, "收到 ERR_NO_AUTH 和 ERR_TIMEOUT 又一次");
+    }
+
+    /// `text` through the pipeline `vars` configure, then back with the
+    /// placeholders left as the provider was sent them.
+    fn through_pipeline(vars: &[(&str, &str)], text: &str) -> (String, String) {
+        let pipeline = Pipeline::from_config(&Config::for_tests(vars));
+        let (sent, contexts) = pipeline.pre(text);
+        let restored = pipeline.post(&sent, &contexts);
+        (sent, restored)
+    }
+
+    #[test]
+    fn preserved_emoji_are_protected_and_restored() {
+        let text = "Great job 👍🏽 see you 🇫🇷";
+        let (sent, restored) = through_pipeline(&[("EMOJI_POLICY", "preserve")], text);
+        assert_eq!(sent, "Great job ⟦E0⟧ see you ⟦E1⟧");
+        assert_eq!(restored, text);
+    }
+
+    #[test]
+    fn stripped_emoji_are_removed() {
+        let (sent, restored) =
+            through_pipeline(&[("EMOJI_POLICY", "strip")], "Great job 👍🏽 see you 🇫🇷");
+        assert_eq!(
+            sent.split_whitespace().collect::<Vec<_>>(),
+            ["Great", "job", "see", "you"]
+        );
+        assert_eq!(restored, sent);
+    }
+
+    #[test]
+    fn translated_emoji_reach_the_provider() {
+        let text = "Great job 👍🏽 see you 🇫🇷";
+        let (sent, _) = through_pipeline(&[("EMOJI_POLICY", "translate")], text);
+        assert_eq!(sent, text);
+    }
+
+    #[test]
+    fn mixed_script_input_is_sent_whole_by_default() {
+        let (sent, _) = through_pipeline(
+            &[("MIXED_SCRIPT_POLICY", "translate")],
+            "安装 Docker 的步骤",
+        );
+        assert_eq!(sent, "安装 Docker 的步骤");
+    }
+
+    #[test]
+    fn protected_mixed_script_keeps_the_minority_script() {
+        let (sent, restored) = through_pipeline(
+            &[("MIXED_SCRIPT_POLICY", "protect")],
+            "安装 Docker Compose 的步骤",
+        );
+        assert_eq!(sent, "安装 ⟦M0⟧ 的步骤");
+        assert_eq!(restored, "安装 Docker Compose 的步骤");
+
+        let (sent, _) = through_pipeline(
+            &[("MIXED_SCRIPT_POLICY", "protect")],
+            "Order the 麻婆豆腐 with rice",
+        );
+        assert_eq!(sent, "Order the ⟦M0⟧ with rice");
+    }
+
+    #[test]
+    fn fenced_code_comes_back_byte_for_byte() {
+        let code = "```rust\nfn main() {\n    println!(\"hello  world\");\n}\n```";
+        let text = format!("Run this:\n{}\nThen check the output.", code);
+        // Whitespace collapsing runs after code protection, so it cannot
+        // touch the indentation or the double space in the string.
+        let pipeline = Pipeline::from_config(&Config::for_tests(&[
+            ("PROTECT_CODE_BLOCKS", "true"),
+            ("COLLAPSE_WHITESPACE", "true"),
+        ]));
+        let (sent, contexts) = pipeline.pre(&text);
+        assert_eq!(sent, "Run this:\n⟦K0⟧\nThen check the output.");
+
+        let translated = sent
+            .replace("Run this:", "运行这个：")
+            .replace("Then check the output.", "然后检查输出。");
+        assert_eq!(
+            pipeline.post(&translated, &contexts),
+            format!("运行这个：\n{}\n然后检查输出。", code)
+        );
+    }
+
+    #[test]
+    fn code_comments_can_be_translated() {
+        let stage = CodeBlockProtect::new(true);
+        let code = "```\nlet x = 1; // set the counter\n```";
+        let (sent, restored) =
+            round_trip(&stage, code, |s| s.replace("set the counter", "设置计数器"));
+        assert_eq!(sent, "⟦K0⟧set the counter⟦K1⟧");
+        assert_eq!(restored, "```\nlet x = 1; // 设置计数器\n```");
+    }
+
+    #[test]
+    fn kept_proper_nouns_are_protected() {
+        let (sent, restored) = through_pipeline(
+            &[
+                ("PROPER_NOUN_POLICY", "keep"),
+                ("PROTECT_PROPER_NOUNS", "true"),
+            ],
+            "Please ask Alice about New York. Then call OpenAI.",
+        );
+        // "Please" and "Then" are only capitalized for opening a sentence.
+        assert_eq!(sent, "Please ask ⟦C0⟧ about ⟦C1⟧. Then call ⟦C2⟧.");
+        assert_eq!(
+            restored,
+            "Please ask Alice about New York. Then call OpenAI."
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use crate::types::LanguageCode;
+use regex::Regex;
+use std::collections::HashMap;
+
+const ENGLISH_WORDS: &[&str] = &[
+    "fuck",
+    "fucking",
+    "fucked",
+    "shit",
+    "bitch",
+    "asshole",
+    "bastard",
+    "cunt",
+    "dick",
+    "motherfucker",
+];
+const CHINESE_WORDS: &[&str] = &["操你妈", "他妈的", "妈的", "傻逼", "王八蛋", "混蛋", "狗屎"];
+
+/// Masks profanity in translated text with asterisks, using a word list per
+/// target language. English matches whole words only; Chinese has no word
+/// boundaries, so any occurrence is masked.
+pub struct ProfanityFilter {
+    patterns: HashMap<LanguageCode, Regex>,
+}
+
+impl ProfanityFilter {
+    /// The built-in lists, extended by `custom`: one word per line, with an
+    /// optional `en:`/`zh:` prefix to limit it to one language. Blank lines
+    /// and lines starting with `#` are skipped.
+    pub fn new(custom: Option<&str>) -> anyhow::Result<Self> {
+        let mut words: HashMap<LanguageCode, Vec<String>> = HashMap::new();
+        for (lang, list) in [
+            (LanguageCode::En, ENGLISH_WORDS),
+            (LanguageCode::Zh, CHINESE_WORDS),
+        ] {
+            words
+                .entry(lang)
+                .or_default()
+                .extend(list.iter().map(|w| w.to_string()));
+        }
+
+        for line in custom.unwrap_or_default().lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (langs, word) = match line.split_once(':') {
+                Some((code, word)) if code.parse::<LanguageCode>().is_ok() => {
+                    (vec![code.parse().unwrap()], word.trim())
+                }
+                _ => (vec![LanguageCode::En, LanguageCode::Zh], line),
+            };
+            for lang in langs {
+                words.entry(lang).or_default().push(word.to_lowercase());
+            }
+        }
+
+        let patterns = words
+            .into_iter()
+            .map(|(lang, mut list)| {
+                // Longest first, so "motherfucker" wins over "fuck".
+                list.sort_by_key(|w| std::cmp::Reverse(w.chars().count()));
+                let alternation = list
+                    .iter()
+                    .map(|w| regex::escape(w))
+                    .collect::<Vec<_>>()
+                    .join("|");
+                let source = match lang {
+                    LanguageCode::Zh => format!("(?i)(?:{})", alternation),
+                    _ => format!(r"(?i)\b(?:{})\b", alternation),
+                };
+                Ok((lang, Regex::new(&source)?))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self { patterns })
+    }
+
+    /// `text` with every listed word for `lang` replaced by asterisks.
+    pub fn mask(&self, text: &str, lang: LanguageCode) -> String {
+        match self.patterns.get(&lang) {
+            Some(pattern) => pattern
+                .replace_all(text, |caps: &regex::Captures| {
+                    "*".repeat(caps[0].chars().count())
+                })
+                .into_owned(),
+            None => text.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listed_words_are_masked_per_language() {
+        let filter = ProfanityFilter::new(None).unwrap();
+        assert_eq!(
+            filter.mask("Well, shit happens. Shitake is fine.", LanguageCode::En),
+            "Well, **** happens. Shitake is fine."
+        );
+        assert_eq!(filter.mask("真是妈的倒霉", LanguageCode::Zh), "真是**倒霉");
+        // English words are not masked in Chinese output, and vice versa.
+        assert_eq!(filter.mask("shit 妈的", LanguageCode::Zh), "shit **");
+    }
+
+    #[test]
+    fn custom_words_are_masked_too() {
+        let filter = ProfanityFilter::new(Some("# house rules\nen:darn\n\n笨蛋")).unwrap();
+        assert_eq!(filter.mask("Darn it", LanguageCode::En), "**** it");
+        assert_eq!(filter.mask("你这个笨蛋", LanguageCode::Zh), "你这个**");
+        assert_eq!(filter.mask("darn", LanguageCode::Zh), "darn");
+    }
+}
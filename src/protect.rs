@@ -0,0 +1,59 @@
+use regex::Regex;
+
+/// Input text with protected spans swapped out for numbered placeholders.
+#[derive(Debug, Clone, Default)]
+pub struct ProtectedText {
+    pub text: String,
+    pub spans: Vec<String>,
+}
+
+/// Placeholder for span `index`; `tag` keeps placeholders from different
+/// protectors apart, e.g. `⟦U0⟧` for a URL and `⟦P0⟧` for a pattern.
+fn placeholder(tag: &str, index: usize) -> String {
+    format!("⟦{}{}⟧", tag, index)
+}
+
+/// Replaces every match of `patterns` with a placeholder the provider is told
+/// to leave alone. Overlapping matches are resolved in favour of the one that
+/// starts first (and is longest when they start together).
+pub fn protect_entities(text: &str, patterns: &[Regex], tag: &str) -> ProtectedText {
+    let matches = patterns
+        .iter()
+        .flat_map(|pattern| pattern.find_iter(text))
+        .map(|m| (m.start(), m.end()))
+        .collect();
+    protect_ranges(text, matches, tag)
+}
+
+/// Like [`protect_entities`], for byte ranges found by other means.
+pub fn protect_ranges(text: &str, mut matches: Vec<(usize, usize)>, tag: &str) -> ProtectedText {
+    matches.retain(|(start, end)| start < end);
+    matches.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+
+    let mut protected = ProtectedText::default();
+    let mut cursor = 0;
+    for (start, end) in matches {
+        if start < cursor {
+            continue;
+        }
+        protected.text.push_str(&text[cursor..start]);
+        protected
+            .text
+            .push_str(&placeholder(tag, protected.spans.len()));
+        protected.spans.push(text[start..end].to_string());
+        cursor = end;
+    }
+    protected.text.push_str(&text[cursor..]);
+
+    protected
+}
+
+/// Inverse of [`protect_entities`]: each placeholder is replaced, in order,
+/// by the span it stood for.
+pub fn restore_entities(text: &str, spans: &[String], tag: &str) -> String {
+    let mut restored = text.to_string();
+    for (index, span) in spans.iter().enumerate() {
+        restored = restored.replacen(&placeholder(tag, index), span, 1);
+    }
+    restored
+}
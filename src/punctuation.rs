@@ -0,0 +1,126 @@
+//! Target-language punctuation and capitalization fixes for model output,
+//! which mixes ASCII and full-width punctuation freely.
+
+use crate::inline::core::is_han;
+use crate::types::LanguageCode;
+
+const HALF_TO_FULL: &[(char, char)] = &[
+    (',', '，'),
+    ('.', '。'),
+    ('?', '？'),
+    ('!', '！'),
+    (':', '：'),
+    (';', '；'),
+];
+const FULL_TO_HALF: &[(char, char)] = &[
+    ('，', ','),
+    ('、', ','),
+    ('。', '.'),
+    ('？', '?'),
+    ('！', '!'),
+    ('：', ':'),
+    ('；', ';'),
+    ('（', '('),
+    ('）', ')'),
+];
+
+/// Converts punctuation to the style of `lang`: full-width after Chinese
+/// characters for Chinese, ASCII for English, where the first letter is also
+/// capitalized.
+pub fn normalize_punctuation(text: &str, lang: LanguageCode) -> String {
+    match lang {
+        LanguageCode::Zh => to_full_width(text),
+        LanguageCode::En => capitalize_first(&to_half_width(text)),
+    }
+}
+
+/// Only punctuation right after a Han character is converted, so numbers,
+/// URLs and embedded English keep theirs; spaces after it are dropped.
+fn to_full_width(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut previous = None;
+    while let Some(c) = chars.next() {
+        let full = HALF_TO_FULL
+            .iter()
+            .find(|(half, _)| *half == c)
+            .map(|(_, full)| *full)
+            // "好..." is an ellipsis, not a full stop
+            .filter(|_| previous.is_some_and(is_han) && chars.peek() != Some(&c));
+        match full {
+            Some(full) => {
+                out.push(full);
+                while chars.next_if(|next| *next == ' ').is_some() {}
+                previous = Some(full);
+            }
+            None => {
+                out.push(c);
+                previous = Some(c);
+            }
+        }
+    }
+    out
+}
+
+/// Full-width punctuation becomes ASCII, followed by a space unless the
+/// text ends or already continues with whitespace or closing punctuation.
+fn to_half_width(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match FULL_TO_HALF.iter().find(|(full, _)| *full == c) {
+            Some((_, half)) => {
+                out.push(*half);
+                let spaced = !matches!(half, '(' | ')')
+                    && chars.peek().is_some_and(|next| {
+                        !next.is_whitespace() && !FULL_TO_HALF.iter().any(|(f, _)| f == next)
+                    });
+                if spaced {
+                    out.push(' ');
+                }
+            }
+            None => out.push(c),
+        }
+    }
+    out
+}
+
+/// Uppercases the first letter when nothing alphanumeric precedes it, so
+/// a leading quote or bracket is skipped but "3 apples" is left alone.
+fn capitalize_first(text: &str) -> String {
+    match text.char_indices().find(|(_, c)| c.is_alphanumeric()) {
+        Some((index, c)) if c.is_lowercase() => {
+            let mut out = String::with_capacity(text.len());
+            out.push_str(&text[..index]);
+            out.extend(c.to_uppercase());
+            out.push_str(&text[index + c.len_utf8()..]);
+            out
+        }
+        _ => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn english_output_is_capitalized_with_ascii_punctuation() {
+        assert_eq!(
+            normalize_punctuation("hello，world。how are you？", LanguageCode::En),
+            "Hello, world. how are you?"
+        );
+        assert_eq!(
+            normalize_punctuation("“fine,” she said", LanguageCode::En),
+            "“Fine,” she said"
+        );
+    }
+
+    #[test]
+    fn chinese_output_gets_full_width_punctuation() {
+        assert_eq!(
+            normalize_punctuation("你好, 世界. 版本 1.2 发布了...", LanguageCode::Zh),
+            "你好，世界。版本 1.2 发布了..."
+        );
+    }
+}
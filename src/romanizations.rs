@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Romanizations behind "Show pinyin" buttons, keyed by the id in the
+/// button's callback data (which is too small to carry the text itself).
+/// Entries expire after `ttl`; the oldest are evicted beyond `capacity`.
+pub struct RomanizationStore {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<HashMap<String, (String, Instant)>>,
+}
+
+impl RomanizationStore {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn insert(&self, key: String, romanized: String) {
+        let mut entries = self.entries.lock().unwrap();
+        let ttl = self.ttl;
+        entries.retain(|_, (_, since)| since.elapsed() < ttl);
+        while entries.len() >= self.capacity.max(1) {
+            let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, (_, since))| *since)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            entries.remove(&oldest);
+        }
+        entries.insert(key, (romanized, Instant::now()));
+    }
+
+    /// Returns the romanization for `key` if it has not expired. Entries stay
+    /// in place, since any recipient of the message may press the button.
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(key)
+            .filter(|(_, since)| since.elapsed() < self.ttl)
+            .map(|(romanized, _)| romanized.clone())
+    }
+}
@@ -0,0 +1,100 @@
+/// Maximum length of a single Telegram text message, in UTF-16 code units.
+pub const MESSAGE_LIMIT: usize = 4096;
+/// Maximum length of a media caption, in UTF-16 code units.
+pub const CAPTION_LIMIT: usize = 1024;
+
+/// Escapes text for use inside Telegram HTML-formatted messages.
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Escapes text for use in Telegram MarkdownV2-formatted messages.
+pub fn escape_markdown_v2(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\_*[]()~`>#+-=|{}.!".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Splits `text` into chunks of at most `limit` UTF-16 code units, preferring
+/// line breaks, then sentence ends, then spaces. A chunk is only cut inside a
+/// word when the word alone exceeds the limit.
+pub fn split_for_telegram(text: &str, limit: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut rest = text.trim();
+
+    while !rest.is_empty() {
+        let end = utf16_prefix_len(rest, limit);
+        if end == rest.len() {
+            chunks.push(rest.to_string());
+            break;
+        }
+
+        let split = find_break(&rest[..end]).unwrap_or(end);
+        let (chunk, tail) = rest.split_at(split);
+        chunks.push(chunk.trim_end().to_string());
+        rest = tail.trim_start();
+    }
+
+    chunks
+}
+
+/// Byte length of the longest prefix of `text` that fits in `limit` UTF-16
+/// code units (at least one character, so splitting always makes progress).
+fn utf16_prefix_len(text: &str, limit: usize) -> usize {
+    let mut units = 0;
+    for (index, c) in text.char_indices() {
+        units += c.len_utf16();
+        if units > limit {
+            return if index == 0 { c.len_utf8() } else { index };
+        }
+    }
+    text.len()
+}
+
+fn find_break(window: &str) -> Option<usize> {
+    if let Some(index) = window.rfind('\n').filter(|&i| i > 0) {
+        return Some(index + 1);
+    }
+
+    let mut next_is_space = false;
+    for (index, c) in window.char_indices().rev() {
+        let ends_sentence =
+            matches!(c, '。' | '！' | '？') || (matches!(c, '.' | '!' | '?') && next_is_space);
+        if ends_sentence && index > 0 {
+            return Some(index + c.len_utf8());
+        }
+        next_is_space = c.is_whitespace();
+    }
+
+    window
+        .char_indices()
+        .rev()
+        .find(|(index, c)| *index > 0 && c.is_whitespace())
+        .map(|(index, _)| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_text_is_split_between_words() {
+        let text = "lorem ".repeat(1500);
+        assert_eq!(text.len(), 9000);
+
+        let chunks = split_for_telegram(&text, MESSAGE_LIMIT);
+        assert_eq!(chunks.len(), 3);
+        for chunk in &chunks {
+            assert!(chunk.encode_utf16().count() <= MESSAGE_LIMIT);
+            assert!(chunk.split(' ').all(|word| word == "lorem"));
+        }
+        assert_eq!(chunks.join(" "), text.trim());
+    }
+}
@@ -1,106 +1,1039 @@
-use crate::config::Config;
-use crate::types::{ProviderTranslationPayload, TranslationRequest, TranslationResult};
+use crate::cache::{MemoryCache, NoopCache, TranslationCache};
+use crate::circuit::CircuitBreaker;
+use crate::config::{CacheBackend, Config, EmojiPolicy, ProperNounPolicy, ProviderFlavor};
+use crate::error::TranslationError;
+use crate::notes::NoteStripper;
+use crate::preprocess::Pipeline;
+use crate::profanity::ProfanityFilter;
+use crate::punctuation::normalize_punctuation;
+use crate::types::{
+    LanguageCode, ProviderTranslationPayload, TranslationRequest, TranslationResult,
+};
 use anyhow::{Context, Result};
-use reqwest::{Client, Url};
+use pinyin::ToPinyin;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER};
+use reqwest::{Certificate, Client, Proxy, Response, StatusCode, Url};
 use serde_json::json;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher, RandomState};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::{Duration, Instant};
-use tracing::warn;
+use tokio::sync::{OnceCell, Semaphore};
+use tokio::task::JoinSet;
+use tracing::{Instrument, debug, info, info_span, warn};
+use uuid::Uuid;
 
-const SYSTEM_PROMPT: &str = "Translate src->tgt. JSON: {\"t\":\"translation\",\"r\":\"romanized_if_zh\"}. No alternatives. No commentary.";
+const PLACEHOLDER_PROMPT: &str = " Copy ⟦…⟧ placeholders unchanged.";
+const REPROMPT: &str = "Respond ONLY with the JSON object, nothing else.";
+/// Text translated by the periodic health probe.
+const HEALTH_CANARY: &str = "ok";
+/// Queue waits longer than this are logged.
+const QUEUE_WARN_THRESHOLD: Duration = Duration::from_millis(500);
+/// Characters of each payload kept when `debug_log_payloads` is on.
+const PAYLOAD_LOG_LIMIT: usize = 2000;
 
+type Outcome = Result<TranslationResult, TranslationError>;
+
+/// Client for an OpenAI-compatible chat completions endpoint.
 pub struct Translator {
     client: Client,
-    config: Config,
+    settings: RwLock<Arc<Settings>>,
     endpoint: Url,
+    circuit: CircuitBreaker,
+    /// Bounds concurrent provider calls; `None` when unlimited.
+    permits: Option<Semaphore>,
+    /// Provider calls in progress, shared by identical concurrent requests.
+    inflight: Mutex<HashMap<TranslationRequest, Arc<OnceCell<Outcome>>>>,
+    reprompts: AtomicU64,
+    /// Outcome of the latest health probe; true until one fails.
+    healthy: AtomicBool,
+    cache: Arc<dyn TranslationCache>,
+    /// Set in safe mode.
+    profanity: Option<ProfanityFilter>,
+}
+
+/// What [`Translator::reload`] swaps out. Each request works with the
+/// settings current when it started.
+struct Settings {
+    config: Config,
+    pipeline: Pipeline,
+    /// Set with `strip_translator_notes`.
+    notes: Option<NoteStripper>,
+}
+
+impl Settings {
+    fn new(config: Config) -> Self {
+        let pipeline = Pipeline::from_config(&config);
+        let notes = config
+            .strip_translator_notes
+            .then(|| NoteStripper::new(&config.translator_note_patterns));
+        Self {
+            config,
+            pipeline,
+            notes,
+        }
+    }
 }
 
 impl Translator {
+    /// Builds the HTTP client and resolves the `chat/completions` endpoint from
+    /// `translation_api_url`.
     pub fn new(config: Config) -> Result<Self> {
-        let client = Client::builder()
+        let mut headers = HeaderMap::new();
+        for (name, value) in &config.extra_headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .with_context(|| format!("Invalid provider header name: {}", name))?;
+            let value = HeaderValue::from_str(value)
+                .with_context(|| format!("Invalid value for provider header {}", name))?;
+            headers.insert(name, value);
+        }
+
+        let mut builder = Client::builder()
             .timeout(Duration::from_millis(config.http_timeout_ms))
-            .build()?;
+            .default_headers(headers);
+        // An explicit proxy replaces the ones from the environment.
+        if let Some(url) = &config.proxy_url {
+            builder = builder.proxy(Proxy::all(url).context("Invalid PROXY_URL")?);
+        } else if !config.use_system_proxy {
+            builder = builder.no_proxy();
+        }
+        if let Some(path) = &config.tls_ca_cert_path {
+            let pem = std::fs::read(path)
+                .with_context(|| format!("Failed to read TLS CA certificates from {}", path))?;
+            let certs = Certificate::from_pem_bundle(&pem)
+                .with_context(|| format!("Invalid TLS CA certificates in {}", path))?;
+            if certs.is_empty() {
+                anyhow::bail!("No certificates found in {}", path);
+            }
+            for cert in certs {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+        if config.danger_accept_invalid_certs {
+            warn!(
+                "DANGER_ACCEPT_INVALID_CERTS is set: provider certificates are not verified, \
+                 so the API key and all translations can be intercepted"
+            );
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        let client = builder.build()?;
 
         let mut endpoint = Url::parse(&config.translation_api_url)?;
-        if !endpoint.path().ends_with("/chat/completions") {
+        if config.provider_flavor == ProviderFlavor::Azure {
+            // Deployment URLs have no trailing slash, so a plain join would
+            // replace the deployment name instead of appending to it.
+            if !endpoint.path().ends_with("/chat/completions") {
+                let path = format!("{}/chat/completions", endpoint.path().trim_end_matches('/'));
+                endpoint.set_path(&path);
+            }
+            if !endpoint.query_pairs().any(|(key, _)| key == "api-version") {
+                endpoint
+                    .query_pairs_mut()
+                    .append_pair("api-version", &config.azure_api_version);
+            }
+        } else if !endpoint.path().ends_with("/chat/completions") {
             endpoint = endpoint.join("chat/completions")?;
         }
 
+        let circuit = CircuitBreaker::new(
+            config.failure_threshold,
+            Duration::from_millis(config.circuit_cooldown_ms),
+        );
+
+        let permits = (config.max_inflight_requests > 0)
+            .then(|| Semaphore::new(config.max_inflight_requests));
+
+        let profanity = if config.safe_mode {
+            let custom = match &config.profanity_words_path {
+                Some(path) => Some(
+                    std::fs::read_to_string(path)
+                        .with_context(|| format!("Failed to read profanity words from {}", path))?,
+                ),
+                None => None,
+            };
+            Some(ProfanityFilter::new(custom.as_deref())?)
+        } else {
+            None
+        };
+
+        let cache: Arc<dyn TranslationCache> = match config.cache_backend {
+            CacheBackend::None => Arc::new(NoopCache),
+            CacheBackend::Memory => Arc::new(MemoryCache::new(
+                Duration::from_secs(config.cache_ttl_secs),
+                config.cache_capacity,
+            )),
+            #[cfg(feature = "redis")]
+            CacheBackend::Redis => Arc::new(crate::cache::RedisCache::new(
+                config.redis_url.as_deref().unwrap_or_default(),
+                config.redis_key_prefix.clone(),
+                config.cache_ttl_secs,
+            )?),
+            #[cfg(not(feature = "redis"))]
+            CacheBackend::Redis => {
+                anyhow::bail!("CACHE_BACKEND=redis needs a build with the redis feature")
+            }
+        };
+
         Ok(Self {
             client,
-            config,
+            settings: RwLock::new(Arc::new(Settings::new(config))),
             endpoint,
+            circuit,
+            permits,
+            inflight: Mutex::new(HashMap::new()),
+            reprompts: AtomicU64::new(0),
+            healthy: AtomicBool::new(true),
+            cache,
+            profanity,
         })
     }
 
-    pub async fn translate(&self, request: TranslationRequest) -> Result<TranslationResult> {
+    /// Sends a single translation request to the provider and parses its reply.
+    ///
+    /// Replies that are not valid JSON are used verbatim as the translation,
+    /// after one stricter reprompt if `reprompt_on_parse_failure` is set.
+    /// Recent successful translations are served from the cache, and identical
+    /// requests made while one is in flight wait for and share its outcome
+    /// instead of calling the provider again.
+    pub async fn translate(&self, request: TranslationRequest) -> Outcome {
+        // Everything logged on the way carries the request id and direction.
+        let span = info_span!(
+            "translate",
+            request_id = %Uuid::new_v4().simple(),
+            direction = %format_args!("{}>{}", request.source_lang, request.target_lang),
+        );
         let start = Instant::now();
-        let prompt = format!(
-            "src={};tgt={};text={}",
-            request.source_lang, request.target_lang, request.text
+        let outcome = self
+            .translate_cached(request)
+            .instrument(span.clone())
+            .await;
+        let latency_ms = start.elapsed().as_millis() as u64;
+        span.in_scope(|| match &outcome {
+            Ok(result) => info!(
+                latency_ms,
+                provider_latency_ms = result.provider_latency_ms,
+                outcome = "ok",
+                "Translation finished"
+            ),
+            Err(e) => info!(latency_ms, outcome = "error", error = %e, "Translation finished"),
+        });
+        outcome
+    }
+
+    async fn translate_cached(&self, request: TranslationRequest) -> Outcome {
+        if let Some(cached) = self.cache.get(&request).await {
+            return Ok(cached);
+        }
+
+        let cell = self
+            .inflight
+            .lock()
+            .unwrap()
+            .entry(request.clone())
+            .or_default()
+            .clone();
+
+        // If the caller driving the call is dropped, a waiting one takes over.
+        let outcome = cell
+            .get_or_init(|| self.translate_uncoalesced(request.clone()))
+            .await
+            .clone();
+
+        {
+            let mut inflight = self.inflight.lock().unwrap();
+            if inflight
+                .get(&request)
+                .is_some_and(|current| Arc::ptr_eq(current, &cell))
+            {
+                inflight.remove(&request);
+            }
+        }
+
+        if let Ok(result) = &outcome {
+            self.cache.put(request, result.clone()).await;
+        }
+        outcome
+    }
+
+    /// Translates `requests` into the cache, at most `concurrency` at a time.
+    /// Failed phrases are logged and skipped.
+    pub async fn warm_up(self: Arc<Self>, requests: Vec<TranslationRequest>, concurrency: usize) {
+        let total = requests.len();
+        let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+        let mut tasks = JoinSet::new();
+        for request in requests {
+            let translator = Arc::clone(&self);
+            let permits = Arc::clone(&permits);
+            tasks.spawn(async move {
+                let _permit = permits.acquire_owned().await;
+                let text = request.text.clone();
+                translator.translate(request).await.map_err(|e| (text, e))
+            });
+        }
+
+        let (mut done, mut failed) = (0, 0);
+        while let Some(joined) = tasks.join_next().await {
+            match joined {
+                Ok(Ok(_)) => done += 1,
+                Ok(Err((text, e))) => {
+                    failed += 1;
+                    warn!("Warmup failed for {:?}: {}", text, e);
+                }
+                Err(e) => {
+                    failed += 1;
+                    warn!("Warmup task failed: {}", e);
+                }
+            }
+            if (done + failed) % 10 == 0 {
+                info!("Cache warmup: {}/{} phrases", done + failed, total);
+            }
+        }
+        info!(
+            "Cache warmup finished: {} translated, {} failed",
+            done, failed
         );
+    }
 
-        let body = json!({
-            "model": self.config.translation_model,
-            "temperature": 0.0,
-            "messages": [
-                { "role": "system", "content": SYSTEM_PROMPT },
-                { "role": "user", "content": prompt }
-            ]
-        });
+    /// Sends a one-word canary translation every `period` and tracks
+    /// whether the provider still answers. The canary goes straight to the
+    /// provider, past the cache, the concurrency limit and the circuit
+    /// breaker, so it neither delays nor affects real requests.
+    pub async fn monitor_health(self: Arc<Self>, period: Duration) {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            self.check_health().await;
+        }
+    }
 
-        let response = self
-            .client
-            .post(self.endpoint.clone())
-            .bearer_auth(&self.config.translation_api_key)
-            .json(&body)
-            .send()
-            .await?;
+    async fn check_health(&self) {
+        let canary = TranslationRequest {
+            text: HEALTH_CANARY.to_string(),
+            source_lang: LanguageCode::En,
+            target_lang: LanguageCode::Zh,
+            alternatives: 0,
+            dictionary: false,
+            romanize_only: false,
+            explain: false,
+            context: None,
+        };
+        let settings = self.settings();
+        let config = &settings.config;
+        let body = request_body(&canary, HEALTH_CANARY, false, config);
+        let started = Instant::now();
+        let outcome = self
+            .send_once(&body, Duration::from_millis(config.http_timeout_ms))
+            .await;
+        let healthy = outcome.is_ok();
+        let was_healthy = self.healthy.swap(healthy, Ordering::Relaxed);
+        match outcome {
+            Err(e) if was_healthy => warn!("Provider health check failed: {}", e),
+            Err(e) => debug!("Provider health check still failing: {}", e),
+            Ok(_) if !was_healthy => info!("Provider health check recovered"),
+            Ok(_) => debug!(
+                "Provider health check passed in {}ms",
+                started.elapsed().as_millis()
+            ),
+        }
+    }
+
+    /// Applies `config` to requests started from now on: model, prompt
+    /// options, temperature, retries, timeouts and preprocessing. The
+    /// endpoint, provider flavor, extra headers, proxy, TLS options, cache,
+    /// concurrency limit and safe mode are fixed at construction and need a
+    /// restart. So does everything the bot's handlers read from their own
+    /// copy of the config: UI language, parsing and detection options
+    /// (`parse_options`), inline result order and `max_inline_results`,
+    /// direction headers, DM formatting, history, audit, channel and
+    /// concurrency settings.
+    pub fn reload(&self, config: Config) {
+        let current = self.settings();
+        let previous = &current.config;
+        if config.translation_api_url != previous.translation_api_url
+            || config.provider_flavor != previous.provider_flavor
+            || config.extra_headers != previous.extra_headers
+            || config.proxy_url != previous.proxy_url
+            || config.use_system_proxy != previous.use_system_proxy
+            || config.tls_ca_cert_path != previous.tls_ca_cert_path
+            || config.danger_accept_invalid_certs != previous.danger_accept_invalid_certs
+        {
+            warn!("Provider endpoint settings changed; restart to apply them");
+        }
+        *self.settings.write().unwrap() = Arc::new(Settings::new(config));
+        info!("Translator settings reloaded");
+    }
+
+    /// The default model, as last set by `reload`; per-direction overrides
+    /// in `model_for` may pick another.
+    pub fn model(&self) -> String {
+        self.settings().config.translation_model.clone()
+    }
+
+    fn settings(&self) -> Arc<Settings> {
+        Arc::clone(&self.settings.read().unwrap())
+    }
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            anyhow::bail!("Translation provider failed ({}): {}", status, text);
+    /// Whether the latest health probe succeeded; always true when probes
+    /// are disabled.
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    /// The translation cache, for the background sweeper.
+    pub fn cache(&self) -> Arc<dyn TranslationCache> {
+        self.cache.clone()
+    }
+
+    async fn translate_uncoalesced(&self, request: TranslationRequest) -> Outcome {
+        let start = Instant::now();
+        let settings = self.settings();
+        let config = &settings.config;
+        if request.romanize_only && request.target_lang.romanization_scheme().is_none() {
+            return Err(TranslationError::RomanizationUnavailable(
+                request.target_lang,
+            ));
+        }
+        if request.romanize_only
+            && config.local_pinyin_fallback
+            && let Some(romanized) = local_pinyin(&request.text)
+        {
+            return Ok(TranslationResult {
+                primary_text: request.text,
+                alternate_texts: Vec::new(),
+                romanized_text: Some(romanized),
+                romanized_is_fallback: true,
+                provider_latency_ms: 0,
+                truncated: false,
+                unchanged: false,
+                part_of_speech: None,
+                example: None,
+                explanation: None,
+            });
         }
 
-        let payload: serde_json::Value = response.json().await?;
+        let (text, contexts) = settings.pipeline.pre(&request.text);
+        let restore = |s: &str| settings.pipeline.post(s, &contexts);
+        let has_placeholders = contexts.iter().any(|ctx| !ctx.spans.is_empty());
+        let body = request_body(&request, &text, has_placeholders, config);
+
+        let deadline = (config.request_budget_ms > 0)
+            .then(|| start + Duration::from_millis(config.request_budget_ms));
+
+        // An open circuit fails fast instead of queueing for a slot first.
+        if !self.circuit.allow() {
+            return Err(TranslationError::Unavailable);
+        }
+        // Queue for a provider slot, but no longer than a request may take.
+        let _permit = match &self.permits {
+            Some(permits) => {
+                let queued = Instant::now();
+                let wait = within_budget(Duration::from_millis(config.http_timeout_ms), deadline);
+                let permit = tokio::time::timeout(wait, permits.acquire())
+                    .await
+                    .ok()
+                    .and_then(Result::ok);
+                let Some(permit) = permit else {
+                    // Nothing was sent, so a probe of a half-open circuit
+                    // says nothing about the provider either.
+                    self.circuit.release_probe();
+                    return Err(TranslationError::Busy);
+                };
+                if queued.elapsed() > QUEUE_WARN_THRESHOLD {
+                    warn!(
+                        queued_ms = queued.elapsed().as_millis() as u64,
+                        "Provider request queued"
+                    );
+                }
+                Some(permit)
+            }
+            None => None,
+        };
+
+        let payload = match self.send_with_retries(&body, deadline).await {
+            Ok(payload) => {
+                self.circuit.record_success();
+                payload
+            }
+            // Only failures that say the provider is unwell count; a refused
+            // request or bad credentials say nothing about the next request.
+            Err(e) if e.is_retriable() => {
+                if self.circuit.record_failure() {
+                    warn!(
+                        cooldown_ms = config.circuit_cooldown_ms,
+                        "Opening provider circuit after repeated failures"
+                    );
+                }
+                return Err(e);
+            }
+            Err(e) => {
+                self.circuit.record_success();
+                return Err(e);
+            }
+        };
+
+        let content = reply_text(&payload)
+            .ok_or_else(|| TranslationError::Malformed("missing content".to_string()))?;
+
+        // The API key only ever travels in the auth header, never here.
+        let system_prompt = body["messages"][0]["content"].as_str().unwrap_or_default();
+        let prompt = body["messages"][1]["content"].as_str().unwrap_or_default();
+        if config.debug_log_payloads {
+            debug!(
+                "Provider exchange: system={:?} user={:?} reply={:?}",
+                cap_for_log(system_prompt),
+                cap_for_log(prompt),
+                cap_for_log(content)
+            );
+        } else if sampled(config.payload_log_sample_rate) {
+            info!(
+                "Sampled provider exchange: system={:?} user={:?} reply={:?}",
+                cap_for_log(system_prompt),
+                cap_for_log(prompt),
+                cap_for_log(content)
+            );
+        }
+
+        let mut parsed = match self.parse_json_content(content) {
+            Some(parsed) => Some(parsed),
+            None if config.reprompt_on_parse_failure => {
+                self.reprompt(&body, content, deadline).await
+            }
+            None => None,
+        }
+        .unwrap_or_else(|| raw_payload(content));
+
+        // An empty "t" is usually a silent refusal; never pass it on as a
+        // blank message.
+        if !request.romanize_only && parsed.translation.trim().is_empty() {
+            match &config.empty_translation_fallback {
+                Some(fallback) => parsed.translation = fallback.clone(),
+                None => return Err(TranslationError::EmptyTranslation),
+            }
+        }
+
+        let mut romanized_text = parsed
+            .romanized
+            .filter(|s| !s.trim().is_empty())
+            .filter(|_| request.target_lang.romanization_scheme().is_some())
+            .map(|s| restore(&s));
+        let romanized_is_fallback = romanized_text.is_none()
+            && config.local_pinyin_fallback
+            && request.target_lang == LanguageCode::Zh;
+        if romanized_is_fallback {
+            romanized_text = local_pinyin(&restore(&parsed.translation));
+        }
+        let romanized_is_fallback = romanized_is_fallback && romanized_text.is_some();
+
+        let clean = |text: String| {
+            let text = if config.normalize_output_punctuation {
+                normalize_punctuation(&text, request.target_lang)
+            } else {
+                text
+            };
+            match &self.profanity {
+                Some(filter) => filter.mask(&text, request.target_lang),
+                None => text,
+            }
+        };
+
+        let polish = |text: &str| {
+            let text = restore(text);
+            match &settings.notes {
+                Some(notes) => clean(notes.strip(&text)),
+                None => clean(text),
+            }
+        };
+
+        // A romanize-only request leaves the text itself untranslated.
+        let translation = if request.romanize_only {
+            request.text.clone()
+        } else {
+            polish(&parsed.translation)
+        };
+        let (primary_text, truncated) = cap_output(translation, config.max_output_chars);
+        if truncated {
+            warn!(
+                "Truncated provider output to {} characters",
+                config.max_output_chars
+            );
+        }
 
-        let content = payload["choices"][0]["message"]["content"]
-            .as_str()
-            .context("Provider response missing content")?;
+        // Alternatives asked for in the prompt come first, then the
+        // translations of any extra completions.
+        let candidates = extra_reply_texts(&payload)
+            .map(|content| {
+                self.parse_json_content(content)
+                    .map_or_else(|| content.trim().to_string(), |p| p.translation)
+            })
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| polish(&s));
+        let mut alternate_texts: Vec<String> = Vec::new();
+        for text in parsed
+            .alternatives
+            .unwrap_or_default()
+            .iter()
+            .take(config.max_alternatives_requested)
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| clean(restore(s)))
+            .chain(candidates)
+        {
+            if alternate_texts.len() == request.alternatives {
+                break;
+            }
+            if text != primary_text && !alternate_texts.contains(&text) {
+                alternate_texts.push(text);
+            }
+        }
 
-        let parsed = self.parse_json_content(content)?;
+        let unchanged = !request.romanize_only && same_text(&primary_text, &request.text);
 
         Ok(TranslationResult {
-            primary_text: parsed.translation,
-            alternate_texts: vec![], // No alternatives to save tokens
-            romanized_text: parsed.romanized.filter(|s| !s.trim().is_empty()),
+            primary_text,
+            alternate_texts,
+            unchanged,
+            romanized_text,
+            romanized_is_fallback,
             provider_latency_ms: start.elapsed().as_millis(),
+            truncated,
+            part_of_speech: parsed
+                .part_of_speech
+                .filter(|_| request.dictionary)
+                .filter(|s| !s.trim().is_empty()),
+            example: parsed
+                .example
+                .filter(|_| request.dictionary)
+                .filter(|s| !s.trim().is_empty())
+                .map(|s| clean(restore(&s))),
+            explanation: parsed
+                .explanation
+                .filter(|_| request.explain)
+                .filter(|s| !s.trim().is_empty())
+                .map(|s| clean(restore(&s))),
         })
     }
 
-    fn parse_json_content(&self, content: &str) -> Result<ProviderTranslationPayload> {
-        // Extract JSON from content (it might be wrapped in markdown code blocks or have extra text)
-        let json_str = if let Some(start) = content.find('{') {
-            if let Some(end) = content.rfind('}') {
-                &content[start..=end]
+    /// Sends `body`, retrying per the config. With a `deadline`, each call's
+    /// timeout is cut to the time left, and no retry starts that could not
+    /// finish before it.
+    async fn send_with_retries(
+        &self,
+        body: &serde_json::Value,
+        deadline: Option<Instant>,
+    ) -> Result<serde_json::Value, TranslationError> {
+        let settings = self.settings();
+        let config = &settings.config;
+        let max_backoff = Duration::from_millis(config.retry_max_backoff_ms);
+        let mut attempt = 0;
+
+        loop {
+            let timeout = within_budget(Duration::from_millis(config.http_timeout_ms), deadline);
+            if timeout.is_zero() {
+                return Err(TranslationError::Timeout);
+            }
+            let error = match self.send_once(body, timeout).await {
+                Ok(payload) => return Ok(payload),
+                Err(error) if error.is_retriable() => error,
+                Err(error) => return Err(error),
+            };
+            let retry_after = match error {
+                TranslationError::RateLimited {
+                    retry_after_secs: Some(secs),
+                } => Some(Duration::from_secs(secs)),
+                _ => None,
+            };
+
+            // The provider's Retry-After stretches our backoff, but never past
+            // the configured maximum: if it asks for longer, give up now.
+            let backoff = backoff(config, attempt).min(max_backoff);
+            let delay = retry_after.map_or(backoff, |wait| wait.max(backoff));
+            if attempt >= config.max_retries || delay > max_backoff {
+                return Err(error);
+            }
+            if within_budget(delay, deadline) < delay {
+                debug!("Request budget spent after {} attempts", attempt + 1);
+                return Err(error);
+            }
+
+            attempt += 1;
+            warn!(
+                "Provider call failed (attempt {}), retrying in {:?}: {}",
+                attempt, delay, error
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    async fn send_once(
+        &self,
+        body: &serde_json::Value,
+        timeout: Duration,
+    ) -> Result<serde_json::Value, TranslationError> {
+        let settings = self.settings();
+        let config = &settings.config;
+        let mut request = self
+            .client
+            .post(self.endpoint.clone())
+            .timeout(timeout)
+            .json(body);
+        if config.provider_flavor == ProviderFlavor::Azure {
+            request = request.header("api-key", &config.translation_api_key);
+        } else if config.provider_bearer_auth {
+            request = request.bearer_auth(&config.translation_api_key);
+        }
+        let response = request.send().await.map_err(network_error)?;
+
+        log_rate_limit_headers(response.headers());
+
+        let status = response.status();
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            return Err(TranslationError::RateLimited {
+                retry_after_secs: parse_retry_after(response.headers()),
+            });
+        }
+        if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+            return Err(TranslationError::Auth);
+        }
+
+        let limit = config.max_response_bytes;
+        let body = read_capped(response, limit).await?;
+
+        if !status.is_success() {
+            let message = String::from_utf8_lossy(&body).into_owned();
+            return Err(if status.is_client_error() {
+                TranslationError::ProviderDeclined(message)
             } else {
-                content
+                TranslationError::Provider {
+                    status: status.as_u16(),
+                    message,
+                }
+            });
+        }
+
+        serde_json::from_slice(&body).map_err(|e| TranslationError::Malformed(e.to_string()))
+    }
+
+    fn parse_json_content(&self, content: &str) -> Option<ProviderTranslationPayload> {
+        // The reply may wrap the object in a code block, or surround it with
+        // reasoning and commentary that contain braces of their own. Take the
+        // first complete object carrying one of our fields, ignoring whatever
+        // follows it.
+        content.match_indices('{').find_map(|(start, _)| {
+            let object = serde_json::Deserializer::from_str(&content[start..])
+                .into_iter::<serde_json::Map<String, serde_json::Value>>()
+                .next()?
+                .ok()?;
+            let ours = ["t", "r", "translation", "romanized"];
+            if !ours.iter().any(|key| object.contains_key(*key)) {
+                return None;
+            }
+            serde_json::from_value(serde_json::Value::Object(object)).ok()
+        })
+    }
+
+    /// Asks once more, showing the provider its unparsable reply, for the
+    /// bare JSON object. Any failure here just means the raw reply is used.
+    async fn reprompt(
+        &self,
+        body: &serde_json::Value,
+        reply: &str,
+        deadline: Option<Instant>,
+    ) -> Option<ProviderTranslationPayload> {
+        let count = self.reprompts.fetch_add(1, Ordering::Relaxed) + 1;
+        info!(
+            "Reprompting provider after unparsable reply ({} so far)",
+            count
+        );
+        let mut body = body.clone();
+        let messages = body["messages"].as_array_mut()?;
+        messages.push(json!({ "role": "assistant", "content": reply }));
+        messages.push(json!({ "role": "user", "content": REPROMPT }));
+        // Only the first reply is read, so one completion is enough.
+        body.as_object_mut()?.remove("n");
+
+        let payload = match self.send_with_retries(&body, deadline).await {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!("Reprompt after unparsable reply failed: {}", e);
+                return None;
             }
-        } else {
-            content
         };
+        self.parse_json_content(reply_text(&payload)?)
+    }
+
+    /// Reprompts sent since startup after replies that were not valid JSON.
+    pub fn reprompt_count(&self) -> u64 {
+        self.reprompts.load(Ordering::Relaxed)
+    }
+}
+
+/// The model's reply: the message content, or when that is empty the
+/// arguments of the first tool call, as sent by providers that deliver
+/// structured output through function calling.
+fn reply_text(payload: &serde_json::Value) -> Option<&str> {
+    choice_text(&payload["choices"][0])
+}
+
+/// Replies of the completions after the first, sent when `n` > 1.
+fn extra_reply_texts(payload: &serde_json::Value) -> impl Iterator<Item = &str> {
+    payload["choices"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .skip(1)
+        .filter_map(choice_text)
+}
+
+fn choice_text(choice: &serde_json::Value) -> Option<&str> {
+    let message = &choice["message"];
+    message["content"]
+        .as_str()
+        .filter(|content| !content.trim().is_empty())
+        .or_else(|| message["tool_calls"][0]["function"]["arguments"].as_str())
+}
+
+/// Whether two texts match, ignoring case and whitespace.
+fn same_text(a: &str, b: &str) -> bool {
+    let words = |text: &str| {
+        text.split_whitespace()
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>()
+    };
+    words(a) == words(b)
+}
+
+fn backoff(config: &Config, attempt: u32) -> Duration {
+    let factor = 2u64.saturating_pow(attempt);
+    Duration::from_millis(config.retry_backoff_ms.saturating_mul(factor))
+}
 
-        match serde_json::from_str::<ProviderTranslationPayload>(json_str) {
-            Ok(parsed) => Ok(parsed),
-            Err(_) => {
-                // Fallback: treat the entire content as the translation
-                warn!("Failed to parse JSON from provider, using raw content as translation");
-                Ok(ProviderTranslationPayload {
-                    translation: content.trim().to_string(),
-                    alternatives: None,
-                    romanized: None,
-                })
+/// Fallback for replies that are not valid JSON: the whole reply is the
+/// translation.
+fn raw_payload(content: &str) -> ProviderTranslationPayload {
+    warn!("Failed to parse JSON from provider, using raw content as translation");
+    ProviderTranslationPayload {
+        translation: content.trim().to_string(),
+        ..Default::default()
+    }
+}
+
+/// The chat completions request for `request`, whose text came out of the
+/// preprocessing pipeline as `text`.
+fn request_body(
+    request: &TranslationRequest,
+    text: &str,
+    has_placeholders: bool,
+    config: &Config,
+) -> serde_json::Value {
+    let prompt = match &request.context {
+        Some(context) => format!(
+            "src={};tgt={};context={};text={}",
+            request.source_lang, request.target_lang, context, text
+        ),
+        None => format!(
+            "src={};tgt={};text={}",
+            request.source_lang, request.target_lang, text
+        ),
+    };
+    let system_prompt = system_prompt(request, has_placeholders, config);
+
+    let chars = request.text.chars().count();
+    let model = config.model_for(request.source_lang, request.target_lang, chars);
+    debug!(chars, model, "Translating");
+    let mut body = json!({
+        "model": model,
+        "temperature": config.temperature_for(request.source_lang, request.target_lang),
+        "messages": [
+            { "role": config.system_role.to_string(), "content": system_prompt },
+            { "role": "user", "content": prompt }
+        ]
+    });
+    if !config.stop_sequences.is_empty() {
+        body["stop"] = json!(config.stop_sequences);
+    }
+    if config.num_candidates > 1 && request.alternatives > 0 && !request.romanize_only {
+        body["n"] = json!(config.num_candidates);
+    }
+    if config.provider_flavor == ProviderFlavor::Azure {
+        // Azure picks the model from the deployment in the URL.
+        body.as_object_mut().unwrap().remove("model");
+    }
+    body
+}
+
+fn system_prompt(request: &TranslationRequest, has_placeholders: bool, config: &Config) -> String {
+    if request.romanize_only {
+        let scheme = request
+            .target_lang
+            .romanization_scheme()
+            .unwrap_or("Pinyin");
+        let mut prompt = format!(
+            "Do not translate. Give the {} of text. JSON: {{\"r\":\"{}\"}}. No commentary.",
+            scheme,
+            scheme.to_lowercase()
+        );
+        if has_placeholders {
+            prompt.push_str(PLACEHOLDER_PROMPT);
+        }
+        return prompt;
+    }
+
+    let mut fields = vec!["\"t\":\"translation\"".to_string()];
+    // Alternatives cost tokens, so they are only requested on demand.
+    if request.alternatives > 0 {
+        fields.push("\"a\":[\"alternative\"]".to_string());
+    }
+    if let Some(scheme) = request.target_lang.romanization_scheme() {
+        fields.push(format!("\"r\":\"{}\"", scheme.to_lowercase()));
+    }
+    if request.dictionary {
+        fields.push("\"p\":\"part of speech\"".to_string());
+        fields.push("\"e\":\"example sentence in tgt\"".to_string());
+    }
+    if request.explain {
+        fields.push("\"x\":\"explanation in tgt\"".to_string());
+    }
+
+    let mut prompt = format!("Translate src->tgt. JSON: {{{}}}.", fields.join(","));
+    if request.alternatives > 0 {
+        prompt.push_str(&format!(
+            " Give up to {} alternatives in \"a\".",
+            request.alternatives.min(config.max_alternatives_requested)
+        ));
+    } else {
+        prompt.push_str(" No alternatives.");
+    }
+    prompt.push_str(" No commentary.");
+    if request.explain {
+        prompt.push_str(
+            " In \"x\", briefly explain any idiomatic or cultural meaning a literal translation misses.",
+        );
+    }
+    if request.context.is_some() {
+        prompt.push_str(" Use context only to pick the right sense; do not translate it.");
+    }
+    if config.emoji_policy == EmojiPolicy::Translate {
+        prompt.push_str(" Render emoji as words.");
+    }
+    match config.proper_noun_policy {
+        ProperNounPolicy::Keep => {
+            prompt.push_str(" Keep names of people, brands and places as written in src.")
+        }
+        ProperNounPolicy::Transliterate => prompt.push_str(
+            " Transliterate names of people, brands and places consistently; do not translate them.",
+        ),
+        ProperNounPolicy::Translate => {}
+    }
+    if config.localize_numerics && !config.protect_numerics {
+        prompt.push_str(" Localize numbers, currency, dates and units to tgt conventions.");
+    }
+    if config.max_output_chars > 0 {
+        prompt.push_str(&format!(
+            " Keep \"t\" under {} characters.",
+            config.max_output_chars
+        ));
+    }
+    if has_placeholders {
+        prompt.push_str(PLACEHOLDER_PROMPT);
+    }
+    prompt
+}
+
+/// Tone-marked pinyin for the Chinese characters in `text`, one syllable per
+/// character; anything else is copied through. `None` without any Chinese.
+fn local_pinyin(text: &str) -> Option<String> {
+    let mut romanized = String::new();
+    let mut found = false;
+    for (c, reading) in text.chars().zip(text.to_pinyin()) {
+        match reading {
+            Some(reading) => {
+                if !romanized.is_empty() && !romanized.ends_with(char::is_whitespace) {
+                    romanized.push(' ');
+                }
+                romanized.push_str(reading.with_tone());
+                found = true;
             }
+            None => romanized.push(c),
         }
     }
+    found.then(|| romanized.trim().to_string())
 }
+
+/// Cuts `text` to `max` characters (0 means no limit), ending in an ellipsis.
+fn cap_output(text: String, max: usize) -> (String, bool) {
+    if max == 0 || text.chars().count() <= max {
+        return (text, false);
+    }
+    let kept: String = text.chars().take(max.saturating_sub(1)).collect();
+    (kept.trim_end().to_string() + "…", true)
+}
+
+fn cap_for_log(text: &str) -> String {
+    if text.chars().count() > PAYLOAD_LOG_LIMIT {
+        text.chars().take(PAYLOAD_LOG_LIMIT).collect::<String>() + "…"
+    } else {
+        text.to_string()
+    }
+}
+
+/// True for roughly `rate` of calls. Each `RandomState` is freshly keyed,
+/// which is random enough for log sampling without an RNG dependency.
+fn sampled(rate: f64) -> bool {
+    if rate <= 0.0 {
+        return false;
+    }
+    let roll = RandomState::new().build_hasher().finish();
+    (roll as f64 / u64::MAX as f64) < rate
+}
+
+/// Reads the response body, giving up as soon as it grows past `limit`
+/// bytes (0 means unlimited) instead of buffering whatever the provider sends.
+async fn read_capped(mut response: Response, limit: usize) -> Result<Vec<u8>, TranslationError> {
+    let too_large = |len: u64| limit > 0 && len > limit as u64;
+    if response.content_length().is_some_and(too_large) {
+        return Err(TranslationError::ResponseTooLarge(limit));
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(network_error)? {
+        body.extend_from_slice(&chunk);
+        if too_large(body.len() as u64) {
+            return Err(TranslationError::ResponseTooLarge(limit));
+        }
+    }
+    Ok(body)
+}
+
+/// `limit`, cut down to what is left before `deadline`, if there is one.
+fn within_budget(limit: Duration, deadline: Option<Instant>) -> Duration {
+    deadline.map_or(limit, |deadline| {
+        limit.min(deadline.saturating_duration_since(Instant::now()))
+    })
+}
+
+fn network_error(error: reqwest::Error) -> TranslationError {
+    if error.is_timeout() {
+        TranslationError::Timeout
+    } else {
+        TranslationError::Network(error.to_string())
+    }
+}
+
+/// Reads a `Retry-After` given in seconds; HTTP-date values are ignored.
+fn parse_retry_after(headers: &HeaderMap) -> Option<u64> {
+    headers.get(RETRY_AFTER)?.to_str().ok()?.trim().parse().ok()
+}
+
+fn log_rate_limit_headers(headers: &HeaderMap) {
+    for (name, value) in headers {
+        if name.as_str().starts_with("x-ratelimit-") {
+            debug!(
+                "Provider {}: {}",
+                name,
+                value.to_str().unwrap_or("<binary>")
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
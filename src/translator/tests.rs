@@ -0,0 +1,799 @@
+use super::*;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::atomic::AtomicUsize;
+
+/// A canned provider reply.
+#[derive(Clone)]
+struct Reply {
+    status: u16,
+    headers: Vec<(&'static str, String)>,
+    body: String,
+    delay: Duration,
+}
+
+impl Reply {
+    /// A successful completion whose content is `{"t": translation}`.
+    fn ok(translation: &str) -> Self {
+        Self::content(&json!({ "t": translation }).to_string())
+    }
+
+    fn content(content: &str) -> Self {
+        Self::status(200).body(json!({ "choices": [{ "message": { "content": content } }] }))
+    }
+
+    fn status(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: "{}".to_string(),
+            delay: Duration::ZERO,
+        }
+    }
+
+    fn header(mut self, name: &'static str, value: &str) -> Self {
+        self.headers.push((name, value.to_string()));
+        self
+    }
+
+    /// Waits `delay` before answering.
+    fn delayed(self, delay: Duration) -> Self {
+        Self { delay, ..self }
+    }
+
+    fn body(self, body: serde_json::Value) -> Self {
+        Self {
+            body: body.to_string(),
+            ..self
+        }
+    }
+}
+
+/// A request as the provider saw it.
+struct Recorded {
+    /// Header names are lowercased.
+    headers: HashMap<String, String>,
+    body: serde_json::Value,
+}
+
+/// A provider on a local port, answering with `replies` in order (the last
+/// one repeats) and recording what it was sent.
+struct MockProvider {
+    url: String,
+    requests: Arc<Mutex<Vec<Recorded>>>,
+}
+
+impl MockProvider {
+    fn start(replies: Vec<Reply>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/v1", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let served = Arc::new(AtomicUsize::new(0));
+        let recorded = Arc::clone(&requests);
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { continue };
+                let replies = replies.clone();
+                let recorded = Arc::clone(&recorded);
+                let served = Arc::clone(&served);
+                std::thread::spawn(move || {
+                    let mut reader = BufReader::new(stream);
+                    let Some(request) = read_request(&mut reader) else {
+                        return;
+                    };
+                    recorded.lock().unwrap().push(request);
+                    let index = served.fetch_add(1, Ordering::SeqCst);
+                    let reply = &replies[index.min(replies.len() - 1)];
+                    std::thread::sleep(reply.delay);
+                    let mut response = format!(
+                        "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+                        reply.status,
+                        reply.body.len()
+                    );
+                    for (name, value) in &reply.headers {
+                        response.push_str(&format!("{}: {}\r\n", name, value));
+                    }
+                    response.push_str("\r\n");
+                    response.push_str(&reply.body);
+                    let _ = reader.get_mut().write_all(response.as_bytes());
+                });
+            }
+        });
+        Self { url, requests }
+    }
+
+    fn hits(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+
+    /// Header `name` of the `index`th request.
+    fn header(&self, index: usize, name: &str) -> Option<String> {
+        self.requests.lock().unwrap()[index]
+            .headers
+            .get(name)
+            .cloned()
+    }
+
+    /// Body of the `index`th request.
+    fn body(&self, index: usize) -> serde_json::Value {
+        self.requests.lock().unwrap()[index].body.clone()
+    }
+}
+
+fn read_request(reader: &mut BufReader<std::net::TcpStream>) -> Option<Recorded> {
+    let mut headers = HashMap::new();
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    loop {
+        line.clear();
+        reader.read_line(&mut line).ok()?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line.split_once(':')?;
+        headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+    }
+    let length = headers
+        .get("content-length")
+        .and_then(|length| length.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).ok()?;
+    let body = serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null);
+    Some(Recorded { headers, body })
+}
+
+/// A translator talking to `provider`, with `vars` on top of the test
+/// defaults: no retries, no cache.
+fn translator(provider: &MockProvider, vars: &[(&str, &str)]) -> Translator {
+    let mut all = vec![
+        ("TRANSLATION_API_URL", provider.url.as_str()),
+        ("PROVIDER_MAX_RETRIES", "0"),
+        ("CACHE_BACKEND", "none"),
+    ];
+    all.extend_from_slice(vars);
+    Translator::new(Config::for_tests(&all)).unwrap()
+}
+
+fn request(text: &str) -> TranslationRequest {
+    TranslationRequest {
+        text: text.to_string(),
+        source_lang: LanguageCode::En,
+        target_lang: LanguageCode::Zh,
+        alternatives: 0,
+        dictionary: false,
+        romanize_only: false,
+        explain: false,
+        context: None,
+    }
+}
+
+#[tokio::test]
+async fn provider_errors_open_the_circuit_and_then_fail_fast() {
+    let provider = MockProvider::start(vec![Reply::status(500)]);
+    let translator = translator(&provider, &[("CIRCUIT_FAILURE_THRESHOLD", "2")]);
+
+    for text in ["one", "two"] {
+        let error = translator.translate(request(text)).await.unwrap_err();
+        assert!(matches!(
+            error,
+            TranslationError::Provider { status: 500, .. }
+        ));
+    }
+    let error = translator.translate(request("three")).await.unwrap_err();
+    assert!(matches!(error, TranslationError::Unavailable));
+    assert_eq!(provider.hits(), 2);
+}
+
+#[tokio::test]
+async fn declined_requests_leave_the_circuit_closed() {
+    let provider = MockProvider::start(vec![Reply::status(400)]);
+    let translator = translator(&provider, &[("CIRCUIT_FAILURE_THRESHOLD", "2")]);
+
+    for text in ["one", "two", "three"] {
+        let error = translator.translate(request(text)).await.unwrap_err();
+        assert!(matches!(error, TranslationError::ProviderDeclined(_)));
+    }
+    assert_eq!(provider.hits(), 3);
+}
+
+#[tokio::test]
+async fn open_circuit_lets_one_probe_through_after_the_cooldown() {
+    let provider = MockProvider::start(vec![
+        Reply::status(500),
+        Reply::status(500),
+        Reply::ok("四"),
+    ]);
+    let translator = translator(
+        &provider,
+        &[
+            ("CIRCUIT_FAILURE_THRESHOLD", "2"),
+            ("CIRCUIT_COOLDOWN_MS", "50"),
+        ],
+    );
+
+    for text in ["one", "two"] {
+        assert!(translator.translate(request(text)).await.is_err());
+    }
+    let error = translator.translate(request("three")).await.unwrap_err();
+    assert!(matches!(error, TranslationError::Unavailable));
+
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    let result = translator.translate(request("four")).await.unwrap();
+    assert_eq!(result.primary_text, "四");
+    assert_eq!(provider.hits(), 3);
+    let prompt = provider.body(2)["messages"][1]["content"].to_string();
+    assert!(prompt.contains("text=four"));
+}
+
+#[tokio::test]
+async fn full_provider_queue_is_reported_as_busy() {
+    let provider = MockProvider::start(vec![Reply::ok("你好")]);
+    let translator = translator(
+        &provider,
+        &[("MAX_INFLIGHT_REQUESTS", "1"), ("HTTP_TIMEOUT_MS", "50")],
+    );
+    let _held = translator
+        .permits
+        .as_ref()
+        .unwrap()
+        .acquire()
+        .await
+        .unwrap();
+
+    let error = translator.translate(request("hello")).await.unwrap_err();
+    assert!(matches!(error, TranslationError::Busy));
+    assert!(error.is_retriable());
+    assert_eq!(provider.hits(), 0);
+}
+
+#[tokio::test]
+async fn open_circuit_fails_without_queueing() {
+    let provider = MockProvider::start(vec![Reply::status(500)]);
+    let translator = translator(
+        &provider,
+        &[
+            ("CIRCUIT_FAILURE_THRESHOLD", "1"),
+            ("MAX_INFLIGHT_REQUESTS", "1"),
+            ("HTTP_TIMEOUT_MS", "5000"),
+        ],
+    );
+    assert!(translator.translate(request("one")).await.is_err());
+    let _held = translator
+        .permits
+        .as_ref()
+        .unwrap()
+        .acquire()
+        .await
+        .unwrap();
+
+    let started = Instant::now();
+    let error = translator.translate(request("two")).await.unwrap_err();
+    assert!(matches!(error, TranslationError::Unavailable));
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn failed_health_check_leaves_real_traffic_alone() {
+    let provider = MockProvider::start(vec![Reply::status(500), Reply::ok("你好")]);
+    let translator = translator(&provider, &[("CIRCUIT_FAILURE_THRESHOLD", "1")]);
+
+    translator.check_health().await;
+    assert!(!translator.is_healthy());
+
+    let result = translator.translate(request("hello")).await.unwrap();
+    assert_eq!(result.primary_text, "你好");
+    assert_eq!(provider.hits(), 2);
+}
+
+#[tokio::test]
+async fn rate_limit_longer_than_the_backoff_cap_is_passed_on() {
+    let provider = MockProvider::start(vec![Reply::status(429).header("Retry-After", "5")]);
+    let translator = translator(&provider, &[("PROVIDER_MAX_RETRIES", "2")]);
+
+    let error = translator.translate(request("hello")).await.unwrap_err();
+    assert!(matches!(
+        error,
+        TranslationError::RateLimited {
+            retry_after_secs: Some(5)
+        }
+    ));
+    assert_eq!(
+        error.to_string(),
+        "Translation provider is busy, try again in 5 seconds"
+    );
+    // Five seconds is past the 4 s default cap, so there is no retry.
+    assert_eq!(provider.hits(), 1);
+}
+
+#[tokio::test]
+async fn rate_limit_within_the_backoff_cap_delays_the_retry() {
+    let provider = MockProvider::start(vec![
+        Reply::status(429).header("Retry-After", "5"),
+        Reply::ok("你好"),
+    ]);
+    let translator = translator(
+        &provider,
+        &[
+            ("PROVIDER_MAX_RETRIES", "1"),
+            ("PROVIDER_RETRY_BACKOFF_MS", "10"),
+            ("PROVIDER_RETRY_MAX_BACKOFF_MS", "6000"),
+        ],
+    );
+
+    let started = Instant::now();
+    let result = translator.translate(request("hello")).await.unwrap();
+    assert_eq!(result.primary_text, "你好");
+    assert!(started.elapsed() >= Duration::from_secs(5));
+    assert_eq!(provider.hits(), 2);
+}
+
+#[tokio::test]
+async fn identical_concurrent_requests_share_one_provider_call() {
+    let provider = MockProvider::start(vec![Reply::ok("你好").delayed(Duration::from_millis(200))]);
+    let translator = Arc::new(translator(&provider, &[]));
+
+    let mut calls = JoinSet::new();
+    for _ in 0..10 {
+        let translator = Arc::clone(&translator);
+        calls.spawn(async move { translator.translate(request("hello")).await });
+    }
+    while let Some(result) = calls.join_next().await {
+        assert_eq!(result.unwrap().unwrap().primary_text, "你好");
+    }
+    assert_eq!(provider.hits(), 1);
+}
+
+#[tokio::test]
+async fn oversized_response_is_refused() {
+    let provider = MockProvider::start(vec![Reply::ok(&"好".repeat(1000))]);
+    let translator = translator(&provider, &[("MAX_RESPONSE_BYTES", "1024")]);
+
+    let error = translator.translate(request("hello")).await.unwrap_err();
+    assert!(matches!(error, TranslationError::ResponseTooLarge(1024)));
+}
+
+#[tokio::test]
+async fn request_budget_cuts_slow_retries_short() {
+    let provider =
+        MockProvider::start(vec![Reply::status(500).delayed(Duration::from_millis(300))]);
+    let translator = translator(
+        &provider,
+        &[
+            ("PROVIDER_MAX_RETRIES", "5"),
+            ("PROVIDER_RETRY_BACKOFF_MS", "10"),
+            ("REQUEST_BUDGET_MS", "500"),
+        ],
+    );
+
+    let started = Instant::now();
+    let error = translator.translate(request("hello")).await.unwrap_err();
+    assert!(matches!(error, TranslationError::Timeout));
+    // Six attempts would take at least 1.8 s.
+    assert!(started.elapsed() < Duration::from_millis(1000));
+    assert_eq!(provider.hits(), 2);
+}
+
+#[tokio::test]
+async fn reasoning_before_the_json_is_skipped() {
+    let provider = MockProvider::start(vec![Reply::content(
+        "Thinking: the set {hello, hi} means a greeting.\n{\"t\": \"你好\"}",
+    )]);
+    let translator = translator(&provider, &[]);
+
+    let result = translator.translate(request("hello")).await.unwrap();
+    assert_eq!(result.primary_text, "你好");
+}
+
+#[tokio::test]
+async fn commentary_after_the_json_is_ignored() {
+    let provider = MockProvider::start(vec![Reply::content(
+        "{\"t\": \"你好\"}\nNote: a casual {informal} greeting.",
+    )]);
+    let translator = translator(&provider, &[]);
+
+    let result = translator.translate(request("hello")).await.unwrap();
+    assert_eq!(result.primary_text, "你好");
+}
+
+#[tokio::test]
+async fn extra_headers_are_sent_with_every_request() {
+    let provider = MockProvider::start(vec![Reply::ok("你好")]);
+    let translator = translator(
+        &provider,
+        &[(
+            "PROVIDER_EXTRA_HEADERS",
+            r#"{"x-api-version": "2024-10-01", "OpenAI-Organization": "org-42"}"#,
+        )],
+    );
+
+    translator.translate(request("hello")).await.unwrap();
+    assert_eq!(
+        provider.header(0, "x-api-version").as_deref(),
+        Some("2024-10-01")
+    );
+    assert_eq!(
+        provider.header(0, "openai-organization").as_deref(),
+        Some("org-42")
+    );
+    assert_eq!(
+        provider.header(0, "authorization").as_deref(),
+        Some("Bearer test-key")
+    );
+}
+
+#[tokio::test]
+async fn bearer_auth_can_be_left_to_a_custom_header() {
+    let provider = MockProvider::start(vec![Reply::ok("你好")]);
+    let translator = translator(
+        &provider,
+        &[
+            ("PROVIDER_EXTRA_HEADERS", r#"{"x-gateway-key": "secret"}"#),
+            ("PROVIDER_BEARER_AUTH", "false"),
+        ],
+    );
+
+    translator.translate(request("hello")).await.unwrap();
+    assert_eq!(
+        provider.header(0, "x-gateway-key").as_deref(),
+        Some("secret")
+    );
+    assert_eq!(provider.header(0, "authorization"), None);
+}
+
+#[test]
+fn invalid_extra_header_names_are_rejected() {
+    let config = Config::for_tests(&[("PROVIDER_EXTRA_HEADERS", r#"{"bad header": "x"}"#)]);
+    assert!(Translator::new(config).is_err());
+}
+
+#[tokio::test]
+async fn unparsable_reply_is_reprompted_for_json() {
+    let provider = MockProvider::start(vec![
+        Reply::content("Sure! Here is the translation you asked for: 你好"),
+        Reply::ok("你好"),
+    ]);
+    let translator = translator(&provider, &[("REPROMPT_ON_PARSE_FAILURE", "true")]);
+
+    let result = translator.translate(request("hello")).await.unwrap();
+    assert_eq!(result.primary_text, "你好");
+    assert_eq!(provider.hits(), 2);
+    let messages = provider.body(1)["messages"].as_array().unwrap().clone();
+    assert_eq!(messages.len(), 4);
+    assert_eq!(messages[2]["role"], "assistant");
+    assert_eq!(messages[3]["content"], REPROMPT);
+    assert_eq!(translator.reprompt_count(), 1);
+}
+
+#[tokio::test]
+async fn unparsable_reply_is_used_raw_without_reprompting() {
+    let provider = MockProvider::start(vec![Reply::content("你好")]);
+    let translator = translator(&provider, &[]);
+
+    let result = translator.translate(request("hello")).await.unwrap();
+    assert_eq!(result.primary_text, "你好");
+    assert_eq!(provider.hits(), 1);
+    assert_eq!(translator.reprompt_count(), 0);
+}
+
+#[tokio::test]
+async fn translation_is_read_from_a_tool_call() {
+    let provider = MockProvider::start(vec![Reply::status(200).body(json!({
+        "choices": [{
+            "message": {
+                "content": null,
+                "tool_calls": [{
+                    "type": "function",
+                    "function": {
+                        "name": "translation",
+                        "arguments": json!({ "t": "你好", "r": "nǐ hǎo" }).to_string()
+                    }
+                }]
+            }
+        }]
+    }))]);
+    let translator = translator(&provider, &[]);
+
+    let result = translator.translate(request("hello")).await.unwrap();
+    assert_eq!(result.primary_text, "你好");
+    assert_eq!(result.romanized_text.as_deref(), Some("nǐ hǎo"));
+}
+
+#[tokio::test]
+async fn output_over_the_budget_is_truncated_and_flagged() {
+    let provider = MockProvider::start(vec![Reply::ok(&"很长的翻译".repeat(10))]);
+    let translator = translator(&provider, &[("MAX_OUTPUT_CHARS", "12")]);
+
+    let result = translator.translate(request("hello")).await.unwrap();
+    assert!(result.truncated);
+    assert_eq!(result.primary_text.chars().count(), 12);
+    assert_eq!(result.primary_text, "很长的翻译很长的翻译很…");
+    let system_prompt = provider.body(0)["messages"][0]["content"].to_string();
+    assert!(system_prompt.contains("under 12 characters"));
+}
+
+#[tokio::test]
+async fn output_within_the_budget_is_untouched() {
+    let provider = MockProvider::start(vec![Reply::ok("你好")]);
+    let translator = translator(&provider, &[("MAX_OUTPUT_CHARS", "12")]);
+
+    let result = translator.translate(request("hello")).await.unwrap();
+    assert!(!result.truncated);
+    assert_eq!(result.primary_text, "你好");
+}
+
+/// The body `request` would be sent with under `vars`.
+fn body_for(vars: &[(&str, &str)], request: &TranslationRequest) -> serde_json::Value {
+    request_body(request, &request.text, false, &Config::for_tests(vars))
+}
+
+#[test]
+fn mapped_direction_uses_its_model() {
+    let vars = [("MODEL_OVERRIDES", r#"{"zh>en": "big-model"}"#)];
+    let zh_to_en = TranslationRequest {
+        source_lang: LanguageCode::Zh,
+        target_lang: LanguageCode::En,
+        ..request("你好")
+    };
+    assert_eq!(body_for(&vars, &zh_to_en)["model"], "big-model");
+    assert_eq!(body_for(&vars, &request("hello"))["model"], "test-model");
+}
+
+#[test]
+fn prompt_is_sent_under_the_configured_role() {
+    let body = body_for(&[], &request("hello"));
+    assert_eq!(body["messages"][0]["role"], "system");
+    let body = body_for(&[("SYSTEM_ROLE", "developer")], &request("hello"));
+    assert_eq!(body["messages"][0]["role"], "developer");
+    assert_eq!(body["messages"][1]["role"], "user");
+}
+
+#[test]
+fn unknown_system_role_is_rejected() {
+    assert!(Config::try_for_tests(&[("SYSTEM_ROLE", "assistant")]).is_err());
+}
+
+#[test]
+fn mapped_direction_uses_its_temperature() {
+    let vars = [
+        ("TEMPERATURE", "0.3"),
+        ("TEMPERATURE_OVERRIDES", r#"{"zh>en": 0}"#),
+    ];
+    let zh_to_en = TranslationRequest {
+        source_lang: LanguageCode::Zh,
+        target_lang: LanguageCode::En,
+        ..request("你好")
+    };
+    assert_eq!(body_for(&vars, &zh_to_en)["temperature"], 0.0);
+    assert_eq!(
+        body_for(&vars, &request("hello"))["temperature"].as_f64(),
+        Some(0.3f32 as f64)
+    );
+}
+
+#[test]
+fn out_of_range_temperature_overrides_are_rejected() {
+    assert!(Config::try_for_tests(&[("TEMPERATURE_OVERRIDES", r#"{"en>zh": 5}"#)]).is_err());
+}
+
+#[tokio::test]
+async fn extra_choices_become_alternatives() {
+    let choice = |translation: &str| json!({ "message": { "content": json!({ "t": translation }).to_string() } });
+    let provider = MockProvider::start(vec![Reply::status(200).body(json!({
+        "choices": [choice("你好"), choice("您好"), choice("嗨")]
+    }))]);
+    let translator = translator(&provider, &[("NUM_CANDIDATES", "3")]);
+
+    let result = translator
+        .translate(TranslationRequest {
+            alternatives: 2,
+            ..request("hello")
+        })
+        .await
+        .unwrap();
+    assert_eq!(provider.body(0)["n"], 3);
+    assert_eq!(result.primary_text, "你好");
+    assert_eq!(result.alternate_texts, ["您好", "嗨"]);
+}
+
+#[tokio::test]
+async fn choices_repeating_the_primary_are_dropped() {
+    let choice = |translation: &str| json!({ "message": { "content": json!({ "t": translation }).to_string() } });
+    let provider = MockProvider::start(vec![Reply::status(200).body(json!({
+        "choices": [choice("你好"), choice("你好"), choice("您好")]
+    }))]);
+    let translator = translator(&provider, &[("NUM_CANDIDATES", "3")]);
+
+    let result = translator
+        .translate(TranslationRequest {
+            alternatives: 2,
+            ..request("hello")
+        })
+        .await
+        .unwrap();
+    assert_eq!(result.alternate_texts, ["您好"]);
+}
+
+#[test]
+fn input_length_picks_the_model_tier() {
+    let vars = [
+        ("FAST_MODEL", "fast-model"),
+        ("ACCURATE_MODEL", "accurate-model"),
+        ("ACCURATE_MODEL_THRESHOLD", "10"),
+    ];
+    assert_eq!(
+        body_for(&vars, &request("123456789"))["model"],
+        "fast-model"
+    );
+    assert_eq!(
+        body_for(&vars, &request("1234567890"))["model"],
+        "accurate-model"
+    );
+    // Tiers left unset fall back to the default model.
+    let vars = [
+        ("ACCURATE_MODEL", "accurate-model"),
+        ("ACCURATE_MODEL_THRESHOLD", "10"),
+    ];
+    assert_eq!(body_for(&vars, &request("short"))["model"], "test-model");
+}
+
+#[test]
+fn stop_sequences_are_sent_only_when_configured() {
+    let body = body_for(
+        &[("STOP_SEQUENCES", r#"["\n\n", "}\n"]"#)],
+        &request("hello"),
+    );
+    assert_eq!(body["stop"], json!(["\n\n", "}\n"]));
+    assert!(body_for(&[], &request("hello")).get("stop").is_none());
+}
+
+#[test]
+fn too_many_stop_sequences_are_rejected() {
+    assert!(Config::try_for_tests(&[("STOP_SEQUENCES", r#"["a", "b", "c", "d", "e"]"#)]).is_err());
+}
+
+#[tokio::test]
+async fn echoed_input_is_flagged_unchanged() {
+    let provider = MockProvider::start(vec![Reply::ok("Docker Compose"), Reply::ok("你好")]);
+    let translator = translator(&provider, &[]);
+
+    let echoed = translator
+        .translate(request("docker  compose"))
+        .await
+        .unwrap();
+    assert!(echoed.unchanged);
+    let translated = translator.translate(request("hello")).await.unwrap();
+    assert!(!translated.unchanged);
+}
+
+#[tokio::test]
+async fn requested_alternatives_are_capped() {
+    let provider = MockProvider::start(vec![Reply::content(
+        &json!({ "t": "你好", "a": ["您好", "嗨", "哈喽", "喂"] }).to_string(),
+    )]);
+    let translator = translator(&provider, &[("MAX_ALTERNATIVES_REQUESTED", "2")]);
+
+    let result = translator
+        .translate(TranslationRequest {
+            alternatives: 5,
+            ..request("hello")
+        })
+        .await
+        .unwrap();
+    let system_prompt = provider.body(0)["messages"][0]["content"].to_string();
+    assert!(system_prompt.contains("Give up to 2 alternatives"));
+    assert_eq!(result.alternate_texts, ["您好", "嗨"]);
+}
+
+#[test]
+fn unreasonable_alternative_caps_are_rejected() {
+    for value in ["0", "11"] {
+        assert!(Config::try_for_tests(&[("MAX_ALTERNATIVES_REQUESTED", value)]).is_err());
+    }
+}
+
+#[tokio::test]
+async fn reloaded_model_is_used_for_the_next_request() {
+    let provider = MockProvider::start(vec![Reply::ok("你好"), Reply::ok("早上好")]);
+    let translator = translator(&provider, &[]);
+
+    translator.translate(request("hello")).await.unwrap();
+    assert_eq!(translator.model(), "test-model");
+    translator.reload(Config::for_tests(&[
+        ("TRANSLATION_API_URL", provider.url.as_str()),
+        ("TRANSLATION_MODEL", "swapped-model"),
+        ("PROVIDER_MAX_RETRIES", "0"),
+        ("CACHE_BACKEND", "none"),
+    ]));
+    assert_eq!(translator.model(), "swapped-model");
+    translator.translate(request("good morning")).await.unwrap();
+
+    assert_eq!(provider.body(0)["model"], "test-model");
+    assert_eq!(provider.body(1)["model"], "swapped-model");
+}
+
+#[tokio::test]
+async fn missing_pinyin_is_filled_in_locally() {
+    let provider = MockProvider::start(vec![Reply::content(r#"{"t": "你好", "r": ""}"#)]);
+    let translator = translator(&provider, &[("LOCAL_PINYIN_FALLBACK", "true")]);
+
+    let result = translator.translate(request("hello")).await.unwrap();
+    assert_eq!(result.romanized_text.as_deref(), Some("nǐ hǎo"));
+    assert!(result.romanized_is_fallback);
+}
+
+#[tokio::test]
+async fn provider_pinyin_is_not_flagged_as_fallback() {
+    let provider = MockProvider::start(vec![Reply::content(r#"{"t": "你好", "r": "nǐ hǎo"}"#)]);
+    let translator = translator(&provider, &[("LOCAL_PINYIN_FALLBACK", "true")]);
+
+    let result = translator.translate(request("hello")).await.unwrap();
+    assert_eq!(result.romanized_text.as_deref(), Some("nǐ hǎo"));
+    assert!(!result.romanized_is_fallback);
+}
+
+#[tokio::test]
+async fn blank_translation_is_an_error() {
+    let provider = MockProvider::start(vec![Reply::ok("  ")]);
+    let translator = translator(&provider, &[]);
+
+    let error = translator.translate(request("hello")).await.unwrap_err();
+    assert!(matches!(error, TranslationError::EmptyTranslation));
+}
+
+#[tokio::test]
+async fn blank_translation_uses_the_configured_fallback() {
+    let provider = MockProvider::start(vec![Reply::ok("  ")]);
+    let translator = translator(
+        &provider,
+        &[("EMPTY_TRANSLATION_FALLBACK", "(no translation)")],
+    );
+
+    let result = translator.translate(request("hello")).await.unwrap();
+    assert_eq!(result.primary_text, "(no translation)");
+}
+
+#[tokio::test]
+async fn romanizing_english_says_it_is_unavailable() {
+    let provider = MockProvider::start(vec![Reply::ok("hello")]);
+    let translator = translator(&provider, &[]);
+
+    let error = translator
+        .translate(TranslationRequest {
+            source_lang: LanguageCode::En,
+            target_lang: LanguageCode::En,
+            romanize_only: true,
+            ..request("hello")
+        })
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        TranslationError::RomanizationUnavailable(LanguageCode::En)
+    ));
+    assert_eq!(error.to_string(), "Romanization is not available for en");
+    assert_eq!(provider.hits(), 0);
+}
+
+#[test]
+fn proper_noun_policy_picks_the_instruction() {
+    let system_prompt = |policy| {
+        body_for(
+            &[("PROPER_NOUN_POLICY", policy)],
+            &request("Ask Alice at OpenAI"),
+        )["messages"][0]["content"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    };
+    assert!(
+        system_prompt("keep")
+            .contains("Keep names of people, brands and places as written in src.")
+    );
+    assert!(system_prompt("transliterate").contains(
+        "Transliterate names of people, brands and places consistently; do not translate them."
+    ));
+    let translate = system_prompt("translate");
+    assert!(!translate.contains("names of people"));
+}
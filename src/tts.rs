@@ -0,0 +1,60 @@
+use crate::config::Config;
+use anyhow::{Context, Result};
+use reqwest::Client;
+use serde_json::json;
+use std::time::Duration;
+
+/// Client for an OpenAI-compatible `audio/speech` endpoint.
+pub struct TtsClient {
+    client: Client,
+    url: String,
+    api_key: Option<String>,
+    model: String,
+    voice: String,
+}
+
+impl TtsClient {
+    /// Returns `None` unless TTS is enabled and an endpoint is configured.
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        let Some(url) = config.tts_api_url.clone().filter(|_| config.tts_enabled) else {
+            return Ok(None);
+        };
+
+        let client = Client::builder()
+            .timeout(Duration::from_millis(config.http_timeout_ms))
+            .build()?;
+
+        Ok(Some(Self {
+            client,
+            url,
+            api_key: config.tts_api_key.clone(),
+            model: config.tts_model.clone(),
+            voice: config.tts_voice.clone(),
+        }))
+    }
+
+    /// Synthesizes `text` as Ogg/Opus, the format Telegram voice messages use.
+    pub async fn synthesize(&self, text: &str) -> Result<Vec<u8>> {
+        let body = json!({
+            "model": self.model,
+            "voice": self.voice,
+            "input": text,
+            "response_format": "opus"
+        });
+
+        let mut request = self.client.post(&self.url).json(&body);
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+        let response = request.send().await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            anyhow::bail!("TTS provider failed ({}): {}", status, text);
+        }
+
+        let audio = response.bytes().await.context("Failed to read TTS audio")?;
+        Ok(audio.to_vec())
+    }
+}
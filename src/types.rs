@@ -1,12 +1,24 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum LanguageCode {
     En,
     Zh,
 }
 
+impl LanguageCode {
+    /// Name of the romanization scheme used for this language, or `None` when
+    /// it is already written in Latin script or has no standard scheme.
+    pub fn romanization_scheme(self) -> Option<&'static str> {
+        match self {
+            LanguageCode::En => None,
+            LanguageCode::Zh => Some("Pinyin"),
+        }
+    }
+}
+
 impl std::fmt::Display for LanguageCode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -28,11 +40,115 @@ impl std::str::FromStr for LanguageCode {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct TranslationRequest {
     pub text: String,
     pub source_lang: LanguageCode,
     pub target_lang: LanguageCode,
+    /// Number of alternative renderings to ask the provider for.
+    pub alternatives: usize,
+    /// Ask for part of speech and an example sentence as well.
+    pub dictionary: bool,
+    /// Only romanize `text`; nothing is translated.
+    pub romanize_only: bool,
+    /// Also ask what the text means idiomatically or culturally.
+    pub explain: bool,
+    /// Disambiguating hint for the provider, e.g. "finance" for "bank".
+    pub context: Option<String>,
+}
+
+impl From<&ParsedInlineQuery> for TranslationRequest {
+    fn from(parsed: &ParsedInlineQuery) -> Self {
+        Self {
+            text: parsed.text.clone(),
+            source_lang: parsed.source_lang,
+            target_lang: parsed.target_lang,
+            alternatives: parsed.alternative_count.unwrap_or(0),
+            dictionary: parsed.dictionary,
+            romanize_only: parsed.romanize_only,
+            explain: parsed.explain,
+            context: parsed.context.clone(),
+        }
+    }
+}
+
+/// What to do with text detected as a language other than English or Chinese.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnsupportedSourcePolicy {
+    /// Fall through to the script heuristics (Latin text is read as English).
+    Guess,
+    /// Refuse with [`ParseError::UnsupportedSource`](crate::error::ParseError).
+    Reject,
+}
+
+impl std::str::FromStr for UnsupportedSourcePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "guess" => Ok(UnsupportedSourcePolicy::Guess),
+            "reject" => Ok(UnsupportedSourcePolicy::Reject),
+            other => Err(format!("unknown unsupported-source policy {:?}", other)),
+        }
+    }
+}
+
+/// What to do with input mixing Chinese and Latin script, like
+/// "安装 Docker 的步骤".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MixedScriptPolicy {
+    /// Translate everything, embedded words of the other script included.
+    Translate,
+    /// Keep runs of the less common script untranslated.
+    Protect,
+}
+
+impl std::str::FromStr for MixedScriptPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "translate" => Ok(MixedScriptPolicy::Translate),
+            "protect" => Ok(MixedScriptPolicy::Protect),
+            other => Err(format!("unknown mixed-script policy {:?}", other)),
+        }
+    }
+}
+
+/// Settings that influence how raw input is parsed.
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    pub default_source: LanguageCode,
+    pub default_target: LanguageCode,
+    /// Tidy whitespace, contractions and common typos in English input.
+    pub normalize_source: bool,
+    /// Upper bound for the `#N` alternatives modifier.
+    pub max_alternatives: usize,
+    /// Look single words up dictionary-style.
+    pub dictionary_mode: bool,
+    /// Entries kept in the detection cache; 0 disables it.
+    pub detection_cache_size: usize,
+    pub unsupported_source: UnsupportedSourcePolicy,
+    /// Always use the default direction, ignoring prefixes and detection.
+    pub lock_direction: bool,
+    /// Recognise the `explain:` prefix.
+    pub explain_mode: bool,
+    /// Characters of input kept per source language, for languages that
+    /// should not use [`DEFAULT_MAX_TEXT_LENGTH`].
+    pub max_text_lengths: HashMap<LanguageCode, usize>,
+}
+
+/// Characters of input kept when no per-language limit is configured.
+pub const DEFAULT_MAX_TEXT_LENGTH: usize = 2048;
+
+impl ParseOptions {
+    /// Characters of input in `lang` kept for translation.
+    pub fn max_text_length(&self, lang: LanguageCode) -> usize {
+        self.max_text_lengths
+            .get(&lang)
+            .copied()
+            .unwrap_or(DEFAULT_MAX_TEXT_LENGTH)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -40,6 +156,19 @@ pub struct ParsedInlineQuery {
     pub text: String,
     pub source_lang: LanguageCode,
     pub target_lang: LanguageCode,
+    /// Alternatives requested with `en>zh#N:`, already clamped.
+    pub alternative_count: Option<usize>,
+    /// A single word, looked up dictionary-style when that mode is enabled.
+    pub dictionary: bool,
+    /// `romanize:` query: return the romanization of the text as it is.
+    pub romanize_only: bool,
+    /// `explain:` query: translate and explain idioms and cultural meaning.
+    pub explain: bool,
+    /// From `en>zh [context]:`; guides the provider but is not translated.
+    pub context: Option<String>,
+    /// How sure detection was of the direction, from 0 to 1; `None` when it
+    /// was given with a prefix or locked.
+    pub detection_confidence: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -48,14 +177,40 @@ pub struct TranslationResult {
     pub alternate_texts: Vec<String>,
     pub romanized_text: Option<String>,
     pub provider_latency_ms: u128,
+    /// `romanized_text` was generated locally rather than by the provider,
+    /// so it may miss context-dependent readings.
+    #[serde(default)]
+    pub romanized_is_fallback: bool,
+    /// `primary_text` was cut to `max_output_chars`.
+    #[serde(default)]
+    pub truncated: bool,
+    /// The provider sent the input back as it was, usually because it is
+    /// already in the target language.
+    #[serde(default)]
+    pub unchanged: bool,
+    /// Dictionary lookups only.
+    #[serde(default)]
+    pub part_of_speech: Option<String>,
+    #[serde(default)]
+    pub example: Option<String>,
+    /// Explain queries only.
+    #[serde(default)]
+    pub explanation: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ProviderTranslationPayload {
-    #[serde(alias = "t")]
+    /// Missing in romanize-only replies.
+    #[serde(alias = "t", default)]
     pub translation: String,
     #[serde(alias = "a")]
     pub alternatives: Option<Vec<String>>,
     #[serde(alias = "r")]
     pub romanized: Option<String>,
+    #[serde(alias = "p")]
+    pub part_of_speech: Option<String>,
+    #[serde(alias = "e")]
+    pub example: Option<String>,
+    #[serde(alias = "x")]
+    pub explanation: Option<String>,
 }
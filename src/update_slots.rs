@@ -0,0 +1,77 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Caps updates handled at once across the whole bot, so a flood of inline
+/// queries is turned away at the door instead of queueing for the provider
+/// past Telegram's deadline. A `max` of 0 means unlimited.
+pub struct UpdateSlots {
+    max: usize,
+    in_flight: AtomicUsize,
+}
+
+/// A taken slot, given back when dropped.
+pub struct UpdateSlot<'a> {
+    slots: &'a UpdateSlots,
+}
+
+impl UpdateSlots {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max,
+            in_flight: AtomicUsize::new(0),
+        }
+    }
+
+    /// Takes a slot, or returns `None` when the bot is at its cap.
+    pub fn try_acquire(&self) -> Option<UpdateSlot<'_>> {
+        self.in_flight
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+                (self.max == 0 || count < self.max).then_some(count + 1)
+            })
+            .ok()?;
+        Some(UpdateSlot { slots: self })
+    }
+
+    /// Updates being handled right now.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Acquire)
+    }
+}
+
+impl Drop for UpdateSlot<'_> {
+    fn drop(&mut self) {
+        self.slots.in_flight.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turns_away_updates_past_the_cap() {
+        let slots = UpdateSlots::new(2);
+        let first = slots.try_acquire();
+        let second = slots.try_acquire();
+        assert!(first.is_some() && second.is_some());
+        assert!(slots.try_acquire().is_none());
+        assert_eq!(slots.in_flight(), 2);
+    }
+
+    #[test]
+    fn dropped_slots_are_given_back() {
+        let slots = UpdateSlots::new(1);
+        let slot = slots.try_acquire();
+        assert!(slots.try_acquire().is_none());
+        drop(slot);
+        assert_eq!(slots.in_flight(), 0);
+        assert!(slots.try_acquire().is_some());
+    }
+
+    #[test]
+    fn zero_means_unlimited() {
+        let slots = UpdateSlots::new(0);
+        let taken: Vec<_> = (0..100).map(|_| slots.try_acquire()).collect();
+        assert!(taken.iter().all(Option::is_some));
+        assert_eq!(slots.in_flight(), 100);
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// How much a user wants to see with each translation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Verbosity {
+    /// The translation alone: no header, romanization or alternatives, and a
+    /// single inline result.
+    Terse,
+    /// Whatever the deployment is configured to show.
+    #[default]
+    Normal,
+    /// Everything available, alternatives included.
+    Rich,
+}
+
+impl std::str::FromStr for Verbosity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "terse" => Ok(Verbosity::Terse),
+            "normal" => Ok(Verbosity::Normal),
+            "rich" => Ok(Verbosity::Rich),
+            other => Err(format!("unknown verbosity {:?}", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for Verbosity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Verbosity::Terse => "terse",
+            Verbosity::Normal => "normal",
+            Verbosity::Rich => "rich",
+        })
+    }
+}
+
+/// Each user's `/verbosity` choice. Kept in memory only, so choices do not
+/// survive a restart; users who never chose are not stored.
+#[derive(Default)]
+pub struct VerbosityStore {
+    choices: Mutex<HashMap<u64, Verbosity>>,
+}
+
+impl VerbosityStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, user_id: u64) -> Verbosity {
+        self.choices
+            .lock()
+            .unwrap()
+            .get(&user_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set(&self, user_id: u64, verbosity: Verbosity) {
+        let mut choices = self.choices.lock().unwrap();
+        if verbosity == Verbosity::default() {
+            choices.remove(&user_id);
+        } else {
+            choices.insert(user_id, verbosity);
+        }
+    }
+
+    /// Forgets `user_id`'s choice; `false` when there was none.
+    pub fn clear(&self, user_id: u64) -> bool {
+        self.choices.lock().unwrap().remove(&user_id).is_some()
+    }
+}